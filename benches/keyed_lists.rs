@@ -0,0 +1,68 @@
+#![feature(test)]
+
+extern crate domafic;
+extern crate test;
+
+use domafic::DomNode;
+use domafic::diff::Reconciler;
+use domafic::mock_dom::MockDom;
+use domafic::tags::{li, ul};
+use test::Bencher;
+
+const ROWS: usize = 1000;
+
+fn render(rows: &[usize]) -> impl DomNode<()> {
+    ul(rows.iter().map(|&row| li(row.to_string()).with_key(row)).collect::<Vec<_>>())
+}
+
+// Alternately renders `first` and `second`, so each iteration applies one of the two changes
+fn bench_rerender(b: &mut Bencher, first: Vec<usize>, second: Vec<usize>) {
+    let dom = MockDom::new();
+    let root = dom.root();
+    let mut reconciler = Reconciler::new(dom, root);
+    reconciler.render(render(&first));
+
+    let mut flip = false;
+    b.iter(|| {
+        flip = !flip;
+        reconciler.render(render(if flip { &second } else { &first }));
+        reconciler.backend().take_operations().len()
+    });
+}
+
+#[bench]
+fn unchanged(b: &mut Bencher) {
+    let rows = (0..ROWS).collect::<Vec<_>>();
+    bench_rerender(b, rows.clone(), rows);
+}
+
+#[bench]
+fn reverse(b: &mut Bencher) {
+    let rows = (0..ROWS).collect::<Vec<_>>();
+    bench_rerender(b, rows.clone(), rows.into_iter().rev().collect());
+}
+
+#[bench]
+fn prepend(b: &mut Bencher) {
+    bench_rerender(b, (1..ROWS).collect(), (0..ROWS).collect());
+}
+
+#[bench]
+fn swap_rows(b: &mut Bencher) {
+    let rows = (0..ROWS).collect::<Vec<_>>();
+    let mut swapped = rows.clone();
+    swapped.swap(1, ROWS - 2);
+    bench_rerender(b, rows, swapped);
+}
+
+#[bench]
+fn shuffle(b: &mut Bencher) {
+    let rows = (0..ROWS).collect::<Vec<_>>();
+    let mut shuffled = rows.clone();
+    let mut seed = 7usize;
+    for i in (1..ROWS).rev() {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        shuffled.swap(i, (seed >> 16) % (i + 1));
+    }
+    bench_rerender(b, rows, shuffled);
+}
@@ -3,9 +3,9 @@ use domafic::tags::{button, div, h1};
 use domafic::listener::on;
 
 // If rendering client-side with asm.js or WebAssembly:
-#[cfg(target_os = "emscripten")]
+#[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 use domafic::web_render::{run, JsIo};
-#[cfg(target_os = "emscripten")]
+#[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 use domafic::KeyIter;
 
 type State = isize;
@@ -16,7 +16,7 @@ enum Msg {
 }
 
 fn main() {
-    #[cfg(target_os = "emscripten")]
+    #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     let update = |state: &mut State, msg: Msg, _: KeyIter, _: &JsIo<Msg>| {
         *state = match msg {
             Msg::Increment => *state + 1,
@@ -40,10 +40,10 @@ fn main() {
     };
 
     // If rendering server-side:
-    #[cfg(not(target_os = "emscripten"))]
+    #[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
     println!("HTML: {}", render(&0));
 
     // If rendering client-side with asm.js or WebAssembly:
-    #[cfg(target_os = "emscripten")]
+    #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     run("body", update, render, 0);
 }
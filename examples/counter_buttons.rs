@@ -1,11 +1,11 @@
 extern crate domafic;
 
-#[cfg(not(target_os = "emscripten"))]
+#[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
 fn main() {
     panic!("This example needs to be run in the browser via the asm.js or WebAssembly targets.")
 }
 
-#[cfg(target_os = "emscripten")]
+#[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 fn main() {
     use domafic::{DomNode, KeyIter};
     use domafic::tags::{button, div, h1};
@@ -1,17 +1,17 @@
 extern crate domafic;
 
-#[cfg(not(target_os = "emscripten"))]
+#[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
 fn main() {
     panic!("This example needs to be run in the browser via the asm.js or WebAssembly targets.")
 }
 
-#[cfg(target_os = "emscripten")]
+#[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 fn main() {
     use domafic::tags::{button, div, h1};
     use domafic::listener::on;
 
     // If rendering client-side with asm.js or WebAssembly:
-    #[cfg(target_os = "emscripten")]
+    #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     use domafic::web_render::{run, JsIo, HttpRequest, HttpResult};
 
     #[derive(Debug, Clone)]
@@ -26,7 +26,7 @@ fn main() {
         Received(String),
     }
 
-    #[cfg(target_os = "emscripten")]
+    #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     let update = |state: &mut State, msg: Msg, _keys, js_io: &JsIo<Msg>| {
         match msg {
             Msg::Echo(message) => {
@@ -60,14 +60,14 @@ fn main() {
     };
 
     // If rendering server-side:
-    #[cfg(not(target_os = "emscripten"))]
+    #[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
     println!("HTML: {}", render(&State {
         request_out: false,
         last_response: None,
     }));
 
     // If rendering client-side with asm.js or WebAssembly:
-    #[cfg(target_os = "emscripten")]
+    #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     run("body", update, render, State {
         request_out: false,
         last_response: None,
@@ -1,11 +1,11 @@
 extern crate domafic;
 
-#[cfg(not(target_os = "emscripten"))]
+#[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
 fn main() {
     panic!("This example needs to be run in the browser via the asm.js or WebAssembly targets.")
 }
 
-#[cfg(target_os = "emscripten")]
+#[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 fn main() {
     use domafic::{DomNode, KeyIter};
     use domafic::AttributeValue::*;
@@ -56,7 +56,6 @@ fn main() {
                 ("type", Str("text")),
                 ("placeholder", Str("What do you have to do?")),
                 ("autofocus", Bool(true)),
-                ("value", OwnedStr(current_value.to_owned())),
             ]),
             (
                 on("input", |event|
@@ -72,7 +71,7 @@ fn main() {
                     } else { Msg::None }
                 )
             )
-        ))
+        )).with_properties([("value", OwnedStr(current_value.to_owned()))])
     };
 
     let render_item = |state: &str| {
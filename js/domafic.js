@@ -0,0 +1,386 @@
+// JavaScript glue for running Domafic applications compiled to `wasm32-unknown-unknown`.
+//
+// Usage:
+//
+//     <script src="domafic.js"></script>
+//     <script>domafic.load('target/wasm32-unknown-unknown/release/examples/todo_mvc.wasm');</script>
+//
+// `load` accepts a URL, a `Response`, or the bytes of the module. It instantiates the module
+// and runs its `main` function, returning a promise for the instance.
+(function(root) {
+    'use strict';
+
+    var HTML_NS = 'http://www.w3.org/1999/xhtml';
+    var ATTR_NS = {
+        xlink: 'http://www.w3.org/1999/xlink',
+        xml: 'http://www.w3.org/XML/1998/namespace',
+        xmlns: 'http://www.w3.org/2000/xmlns/'
+    };
+
+    // Thrown by `unwind` to return from `main` once the application is running
+    var UNWIND = {};
+
+    var encoder = new TextEncoder();
+    var decoder = new TextDecoder('utf-8');
+
+    function create(exports) {
+        // DOM nodes referenced from Rust by index
+        var pool = [];
+        var pool_free = [];
+
+        // Arguments of the event or HTTP response currently being handled
+        var args = [];
+        // Encoded string waiting to be copied out by `take_str`
+        var pending = null;
+
+        function alloc(value) {
+            if (!value) { return -1; }
+            var index = pool_free.pop();
+            if (index !== undefined) { pool[index] = value; return index; }
+            return pool.push(value) - 1;
+        }
+
+        function str(ptr, len) {
+            return decoder.decode(new Uint8Array(exports.memory.buffer, ptr, len));
+        }
+
+        function set_pending(value) {
+            if (value === null || value === undefined) { return -1; }
+            pending = encoder.encode(String(value));
+            return pending.length;
+        }
+
+        function call(callback, context, arg, call_args) {
+            var previous = args;
+            args = call_args;
+            try {
+                exports.__domafic_callback(callback, context, arg);
+            } finally {
+                args = previous;
+            }
+        }
+
+        function first_unclaimed(parent, index) {
+            return index == 0 ? parent.firstChild : parent.children[index - 1].nextSibling;
+        }
+
+        function is_ignorable(node) {
+            return node.nodeType == 8 || (node.nodeType == 3 && !/\S/.test(node.data));
+        }
+
+        function wrap_text(text) {
+            var elem = document.createElement('span');
+            elem.appendChild(document.createTextNode(text));
+            return elem;
+        }
+
+        return {
+            unwind: function() {
+                throw UNWIND;
+            },
+
+            element_from_selector: function(ptr, len) {
+                return alloc(document.querySelector(str(ptr, len)));
+            },
+
+            create_element: function(tag_ptr, tag_len, ns_ptr, ns_len) {
+                var tag = str(tag_ptr, tag_len);
+                return alloc(ns_len ?
+                    document.createElementNS(str(ns_ptr, ns_len), tag) :
+                    document.createElement(tag));
+            },
+
+            create_text_node: function(ptr, len) {
+                return alloc(wrap_text(str(ptr, len)));
+            },
+
+            create_raw_html: function(ptr, len) {
+                var elem = document.createElement('span');
+                elem.innerHTML = str(ptr, len);
+                return alloc(elem);
+            },
+
+            release: function(id) {
+                delete pool[id];
+                pool_free.push(id);
+            },
+
+            insert: function(parent_id, index, child_id) {
+                var parent = pool[parent_id];
+                if (index > parent.children.length) { return -1; }
+                if (index == parent.children.length) {
+                    parent.appendChild(pool[child_id]);
+                } else {
+                    parent.insertBefore(pool[child_id], parent.children[index]);
+                }
+                return 0;
+            },
+
+            move_child: function(parent_id, old_index, new_index) {
+                var parent = pool[parent_id];
+                if (old_index >= parent.children.length) { return -1; }
+                if (new_index >= parent.children.length) { return -1; }
+                var elem = parent.removeChild(parent.children[old_index]);
+                if (new_index == parent.children.length) {
+                    parent.appendChild(elem);
+                } else {
+                    parent.insertBefore(elem, parent.children[new_index]);
+                }
+                return 0;
+            },
+
+            remove_self: function(id) {
+                var elem = pool[id];
+                if (elem.parentNode) { elem.parentNode.removeChild(elem); }
+            },
+
+            remove_all_children: function(id) {
+                var elem = pool[id];
+                while (elem.hasChildNodes()) { elem.removeChild(elem.lastChild); }
+            },
+
+            // Listens for one type of event on behalf of every node under the root. The path of
+            // child indices from the root to the target is passed after the event's fields.
+            // Listening during the capture phase also catches events that don't bubble.
+            delegate: function(root_id, name_ptr, name_len, callback, context) {
+                var root = pool[root_id];
+                root.addEventListener(str(name_ptr, name_len), function(event) {
+                    event = event || window.event;
+                    var path = [];
+                    var node = event.target;
+                    if (node && node.nodeType != 1) { node = node.parentNode; }
+                    while (node !== root) {
+                        var parent = node && node.parentNode;
+                        if (!parent) { return; }
+                        path.unshift(Array.prototype.indexOf.call(parent.children, node));
+                        node = parent;
+                    }
+                    call(callback, context, 0, [
+                        event.type || null,
+                        (event.target && event.target.value) ? event.target.value : null,
+                        Math.floor(event.clientX || 0), Math.floor(event.clientY || 0),
+                        Math.floor(event.offsetX || 0), Math.floor(event.offsetY || 0),
+                        event.which || event.keyCode || 0,
+                        event.shiftKey ? 1 : 0,
+                        event.altKey ? 1 : 0,
+                        event.ctrlKey ? 1 : 0,
+                        event.metaKey ? 1 : 0,
+                        event.bubbles ? 1 : 0,
+                        path.length
+                    ].concat(path));
+                }, true);
+            },
+
+            claim_child: function(parent_id, index, kind, ptr, len, ns_ptr, ns_len) {
+                var parent = pool[parent_id];
+                var value = str(ptr, len);
+                var ns = ns_len ? str(ns_ptr, ns_len) : HTML_NS;
+                var node = first_unclaimed(parent, index);
+                var wrap = function(first, count) {
+                    var span = document.createElement('span');
+                    parent.insertBefore(span, first);
+                    for (var i = 0; i < count; i++) { span.appendChild(span.nextSibling); }
+                    return span;
+                };
+                if (kind == 1 && value.length == 0) {
+                    var empty = wrap_text('');
+                    parent.insertBefore(empty, node);
+                    return alloc(empty);
+                }
+                while (node) {
+                    if (kind == 0) {
+                        if (node.nodeType == 1 && node.localName == value &&
+                            node.namespaceURI == ns) { return alloc(node); }
+                    } else if (kind == 1) {
+                        if (node.nodeType == 3 && node.data.slice(0, value.length) == value) {
+                            if (node.data.length > value.length) { node.splitText(value.length); }
+                            return alloc(wrap(node, 1));
+                        }
+                    } else {
+                        var template = document.createElement('span');
+                        template.innerHTML = value;
+                        var count = template.childNodes.length;
+                        var current = node;
+                        for (var i = 0; i < count && current; i++) {
+                            if (!current.isEqualNode(template.childNodes[i])) { break; }
+                            current = current.nextSibling;
+                        }
+                        if (i == count) { return alloc(wrap(node, count)); }
+                    }
+                    if (!is_ignorable(node)) { break; }
+                    var next = node.nextSibling;
+                    parent.removeChild(node);
+                    node = next;
+                }
+                return -1;
+            },
+
+            attribute_count: function(id) {
+                return pool[id].attributes.length;
+            },
+
+            attribute_name: function(id, index) {
+                return set_pending(pool[id].attributes[index].name);
+            },
+
+            has_attribute: function(id, key_ptr, key_len, value_ptr, value_len) {
+                var value = pool[id].getAttribute(str(key_ptr, key_len));
+                return value === str(value_ptr, value_len) ? 1 : 0;
+            },
+
+            remove_unclaimed_children: function(parent_id, index) {
+                var parent = pool[parent_id];
+                var node = first_unclaimed(parent, index);
+                while (node) {
+                    var next = node.nextSibling;
+                    if (!is_ignorable(node)) {
+                        console.warn('domafic: removing unexpected server-rendered node', node);
+                    }
+                    parent.removeChild(node);
+                    node = next;
+                }
+            },
+
+            warn_hydration_mismatch: function(parent_id, index, ptr, len) {
+                console.warn('domafic: hydration mismatch in', pool[parent_id],
+                    'at child ' + index + ': expected ' + str(ptr, len));
+            },
+
+            // Text nodes are wrapped in a `span` by `create_text_node`
+            set_text: function(id, ptr, len) {
+                pool[id].firstChild.nodeValue = str(ptr, len);
+            },
+
+            set_attribute: function(id, key_ptr, key_len, value_ptr, value_len) {
+                var elem = pool[id];
+                var key = str(key_ptr, key_len);
+                var value = str(value_ptr, value_len);
+                var prefix = key.split(':')[0];
+                if (key.indexOf(':') > 0 && ATTR_NS[prefix]) {
+                    elem.setAttributeNS(ATTR_NS[prefix], key, value);
+                } else {
+                    elem.setAttribute(key, value);
+                }
+            },
+
+            remove_attribute: function(id, key_ptr, key_len) {
+                var elem = pool[id];
+                var key = str(key_ptr, key_len);
+                var prefix = key.split(':')[0];
+                if (key.indexOf(':') > 0 && ATTR_NS[prefix]) {
+                    elem.removeAttributeNS(ATTR_NS[prefix], key.slice(prefix.length + 1));
+                } else {
+                    elem.removeAttribute(key);
+                }
+            },
+
+            // `kind` is 1 for booleans and 2 for numbers, which are passed as strings
+            set_property: function(id, key_ptr, key_len, value_ptr, value_len, kind) {
+                var elem = pool[id];
+                var key = str(key_ptr, key_len);
+                var value = str(value_ptr, value_len);
+                if (kind == 1) {
+                    value = value == 'true';
+                } else if (kind == 2) {
+                    value = Number(value);
+                }
+                // Only assign changed values, so that the element (such as the cursor of an
+                // `input`) isn't disturbed
+                if (elem[key] !== value) { elem[key] = value; }
+            },
+
+            // Resets the property to the empty value of its type
+            remove_property: function(id, key_ptr, key_len) {
+                var elem = pool[id];
+                var key = str(key_ptr, key_len);
+                var value = elem[key];
+                elem[key] = typeof value === 'boolean' ? false :
+                    (typeof value === 'number' ? 0 : '');
+            },
+
+            http: function(method_ptr, method_len, url_ptr, url_len, body_ptr, body_len,
+                           headers_ptr, headers_len, timeout, callback, context) {
+                var method = str(method_ptr, method_len);
+                var url = str(url_ptr, url_len);
+                var body = str(body_ptr, body_len);
+                var headers = str(headers_ptr, headers_len).split('\r\n');
+                var xhr = new XMLHttpRequest();
+                var error_fn = function(error_sig) { return function() {
+                    call(callback, context, error_sig, []);
+                } };
+                xhr.addEventListener('timeout', error_fn(1));
+                xhr.addEventListener('error', error_fn(2));
+                xhr.addEventListener('load', function() {
+                    call(callback, context, 0, [
+                        xhr.status, xhr.statusText, xhr.responseText, xhr.getAllResponseHeaders()
+                    ]);
+                });
+                // Report failures asynchronously, as the application is in the middle of an update
+                try { xhr.open(method, url, true); } catch (e) { setTimeout(error_fn(2), 0); return; }
+                for (var i = 0; i < headers.length; i++) {
+                    var split = headers[i].indexOf(':');
+                    if (split > 0) {
+                        xhr.setRequestHeader(
+                            headers[i].slice(0, split), headers[i].slice(split + 1).trim());
+                    }
+                }
+                xhr.responseType = 'text';
+                if (timeout != 0) { xhr.timeout = timeout; }
+                xhr.send(body);
+            },
+
+            arg_int: function(index) {
+                return args[index] | 0;
+            },
+
+            arg_str: function(index) {
+                return set_pending(args[index]);
+            },
+
+            state_json: function(ptr, len) {
+                var elem = document.getElementById(str(ptr, len));
+                return set_pending(elem ? elem.textContent : null);
+            },
+
+            take_str: function(ptr) {
+                new Uint8Array(exports.memory.buffer, ptr, pending.length).set(pending);
+                pending = null;
+            }
+        };
+    }
+
+    function instantiate(source, imports) {
+        if (source instanceof ArrayBuffer || ArrayBuffer.isView(source)) {
+            return WebAssembly.instantiate(source, imports);
+        }
+        var response = typeof source === 'string' ? fetch(source) : Promise.resolve(source);
+        return response
+            .then(function(response) { return response.arrayBuffer(); })
+            .then(function(bytes) { return WebAssembly.instantiate(bytes, imports); });
+    }
+
+    function load(source) {
+        // `exports` is filled in once the module is instantiated, before any imports are called
+        var exports = {};
+        var imports = { domafic: create(exports) };
+        return instantiate(source, imports).then(function(result) {
+            var instance = result.instance;
+            for (var name in instance.exports) { exports[name] = instance.exports[name]; }
+            if (exports.main) {
+                try {
+                    exports.main(0, 0);
+                } catch (e) {
+                    if (e !== UNWIND) { throw e; }
+                }
+            }
+            return instance;
+        });
+    }
+
+    var domafic = { load: load };
+    if (typeof module === 'object' && module.exports) {
+        module.exports = domafic;
+    } else {
+        root.domafic = domafic;
+    }
+})(this);
@@ -0,0 +1,280 @@
+use {AttributeValue, DomNode, DomNodes, DomValue, Event, KeyValue, Listener, Listeners};
+use processors::{DomNodeProcessor, EmptyListeners, ListenerProcessor};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::any::Any;
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::rc::Rc;
+
+/// A `DomNode` whose concrete type has been erased.
+///
+/// Every `DomNode` built from tags and tuples has its own deeply nested type, which makes it
+/// impossible to write recursive views (such as tree views or comment threads) or to choose
+/// between many different kinds of components at runtime. Boxing a node hides its type behind
+/// `BoxedNode<Message>`, which can be stored in `Vec`s, returned from recursive functions, and
+/// used as a child of any other `DomNode`.
+///
+/// Boxing moves the node to the heap and builds a tree of `BoxedNode`s pointing into it, one
+/// for each node of its subtree. Tags, text, attributes, properties and listeners are all
+/// borrowed from the original node rather than copied. `BoxedNode`s nested inside of the boxed
+/// node get a view of their own like any other node, so boxing every level of a deep recursive
+/// view still adds a view of each level below it.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::DomNode;
+/// use domafic::boxed::BoxedNode;
+/// use domafic::tags::{div, li, ul};
+///
+/// struct Comment {
+///     text: String,
+///     replies: Vec<Comment>,
+/// }
+///
+/// fn render_comment(comment: &Comment) -> BoxedNode<()> {
+///     div((
+///         comment.text.clone(),
+///         ul(comment.replies.iter().map(|reply| li(render_comment(reply))).collect::<Vec<_>>()),
+///     )).boxed()
+/// }
+///
+/// let thread = Comment {
+///     text: "First!".to_string(),
+///     replies: vec![Comment { text: "Second".to_string(), replies: vec![] }],
+/// };
+///
+/// assert_eq!(
+///     "<div>First&#33;<ul><li><div>Second<ul></ul></div></li></ul></div>",
+///     render_comment(&thread).to_string()
+/// );
+/// ```
+pub struct BoxedNode<Message: 'static> {
+    // The node in `owner` that this view stands for
+    node: *const dyn ErasedNode<Message>,
+    // The original node. Only the `BoxedNode` returned by `new` owns it: the views of its
+    // descendants can only be borrowed from that one, so they never outlive it
+    owner: Option<Rc<dyn ErasedNode<Message>>>,
+    children: Vec<BoxedNode<Message>>,
+    listeners: BoxedListeners<Message>,
+}
+
+impl<M: 'static> BoxedNode<M> {
+    /// Erases the type of `node` by moving it to the heap.
+    pub fn new<T: DomNode<M> + 'static>(node: T) -> BoxedNode<M> {
+        // Boxing an already boxed node would only add another level of views
+        let mut node = Some(node);
+        if let Some(boxed) = (&mut node as &mut dyn Any).downcast_mut::<Option<BoxedNode<M>>>() {
+            return boxed.take().unwrap();
+        }
+
+        let owner: Rc<dyn ErasedNode<M>> = Rc::new(node.unwrap());
+        // The original node is never moved out of the `Rc` or modified, and it's kept alive for
+        // as long as the views are
+        let mut boxed = unsafe { view(&*owner) };
+        boxed.owner = Some(owner);
+        boxed
+    }
+
+    fn node(&self) -> &dyn ErasedNode<M> {
+        // Views are only reachable through the `BoxedNode` owning the original node
+        unsafe { &*self.node }
+    }
+}
+
+impl<M: 'static> DomNodes<M> for BoxedNode<M> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M: 'static> DomNode<M> for BoxedNode<M> {
+    type Children = Vec<BoxedNode<M>>;
+    type Listeners = BoxedListeners<M>;
+    type WithoutListeners = BoxedNodeWithoutListeners<M>;
+
+    fn key(&self) -> Option<u32> { self.node().erased_key() }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.node().erased_attribute(index)
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.node().erased_property(index)
+    }
+    fn children(&self) -> &Self::Children {
+        &self.children
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        &self.listeners
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (&self.children, &self.listeners)
+    }
+    fn split_listeners(mut self) -> (Self::WithoutListeners, Self::Listeners) {
+        let listeners = BoxedListeners {
+            _owner: self.owner.clone(),
+            listeners: mem::take(&mut self.listeners.listeners),
+        };
+        (BoxedNodeWithoutListeners(self), listeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.node().erased_value() }
+}
+
+impl<M: 'static> fmt::Display for BoxedNode<M> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.displayable().fmt(formatter)
+    }
+}
+
+/// A `BoxedNode` whose listeners have been split off.
+pub struct BoxedNodeWithoutListeners<Message: 'static>(BoxedNode<Message>);
+
+impl<M: 'static> DomNodes<M> for BoxedNodeWithoutListeners<M> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M: 'static> DomNode<M> for BoxedNodeWithoutListeners<M> {
+    type Children = Vec<BoxedNode<M>>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+
+    fn key(&self) -> Option<u32> { self.0.key() }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.0.get_attribute(index)
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.0.get_property(index)
+    }
+    fn children(&self) -> &Self::Children {
+        self.0.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        &EmptyListeners
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (self.0.children(), &EmptyListeners)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.0.value() }
+}
+
+/// The listeners of a `BoxedNode`.
+pub struct BoxedListeners<Message: 'static> {
+    // Keeps the original node alive once the listeners have been split off of the root
+    _owner: Option<Rc<dyn ErasedNode<Message>>>,
+    listeners: Vec<BoxedListener<Message>>,
+}
+
+impl<M: 'static> Listeners<M> for BoxedListeners<M> {
+    fn process_all<'a, P: ListenerProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        for listener in &self.listeners {
+            P::get_processor()(acc, listener)?;
+        }
+        Ok(())
+    }
+}
+
+// A listener of the original node
+struct BoxedListener<M: 'static>(*const dyn Listener<M>);
+
+impl<M: 'static> Listener<M> for BoxedListener<M> {
+    fn event_type_handled(&self) -> &'static str {
+        // Like views, listeners are only reachable while the original node is alive
+        unsafe { (*self.0).event_type_handled() }
+    }
+    fn handle_event(&self, event: Event) -> M {
+        unsafe { (*self.0).handle_event(event) }
+    }
+}
+
+/// Object-safe interface to a `DomNode`, through which views read the original node.
+trait ErasedNode<M> {
+    fn erased_key(&self) -> Option<u32>;
+    fn erased_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)>;
+    fn erased_property(&self, index: usize) -> Option<&KeyValue>;
+    fn erased_value(&self) -> DomValue<'_>;
+    fn process_children<'a>(&'a self, f: &mut dyn FnMut(&'a dyn ErasedNode<M>));
+    fn process_listeners<'a>(&'a self, f: &mut dyn FnMut(&'a dyn Listener<M>));
+}
+
+impl<M: 'static, T: DomNode<M>> ErasedNode<M> for T {
+    fn erased_key(&self) -> Option<u32> { self.key() }
+    fn erased_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.get_attribute(index)
+    }
+    fn erased_property(&self, index: usize) -> Option<&KeyValue> {
+        self.get_property(index)
+    }
+    fn erased_value(&self) -> DomValue<'_> { self.value() }
+    fn process_children<'a>(&'a self, mut f: &mut dyn FnMut(&'a dyn ErasedNode<M>)) {
+        match self.children().process_all::<EachChild>(&mut f) {
+            Ok(()) => {},
+            Err(never) => match never {},
+        }
+    }
+    fn process_listeners<'a>(&'a self, mut f: &mut dyn FnMut(&'a dyn Listener<M>)) {
+        match self.listeners().process_all::<EachListener>(&mut f) {
+            Ok(()) => {},
+            Err(never) => match never {},
+        }
+    }
+}
+
+// Views `node` and its descendants.
+//
+// The views point into `node`, so it must not be moved, modified or dropped while they're alive.
+unsafe fn view<M: 'static>(node: &dyn ErasedNode<M>) -> BoxedNode<M> {
+    let mut children = Vec::new();
+    node.process_children(&mut |child| children.push(view(child)));
+    let mut listeners = Vec::new();
+    node.process_listeners(&mut |listener| listeners.push(BoxedListener(
+        mem::transmute::<&dyn Listener<M>, *const dyn Listener<M>>(listener)
+    )));
+
+    BoxedNode {
+        node: mem::transmute::<&dyn ErasedNode<M>, *const dyn ErasedNode<M>>(node),
+        owner: None,
+        children,
+        listeners: BoxedListeners { _owner: None, listeners },
+    }
+}
+
+struct EachChild<'f>(PhantomData<&'f ()>);
+impl<'a: 'f, 'f, M: 'static> DomNodeProcessor<'a, M> for EachChild<'f> {
+    type Acc = &'f mut dyn FnMut(&'a dyn ErasedNode<M>);
+    type Error = Infallible;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn each<'a, M: 'static, T: DomNode<M>>(
+            f: &mut &mut dyn FnMut(&'a dyn ErasedNode<M>),
+            node: &'a T,
+        ) -> Result<(), Infallible> {
+            f(node);
+            Ok(())
+        }
+        each
+    }
+}
+
+struct EachListener<'f>(PhantomData<&'f ()>);
+impl<'a: 'f, 'f, M: 'static> ListenerProcessor<'a, M> for EachListener<'f> {
+    type Acc = &'f mut dyn FnMut(&'a dyn Listener<M>);
+    type Error = Infallible;
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn each<'a, M, L: Listener<M>>(
+            f: &mut &mut dyn FnMut(&'a dyn Listener<M>),
+            listener: &'a L,
+        ) -> Result<(), Infallible> {
+            f(listener);
+            Ok(())
+        }
+        each
+    }
+}
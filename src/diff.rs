@@ -0,0 +1,911 @@
+use {AttributeValue, DomNode, DomValue, Event, KeyIter, KeyValue, Listener, Namespace};
+use keys::Keys;
+use processors::{DomNodes, DomNodeProcessor, Listeners, ListenerProcessor};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::mem;
+
+/// A DOM implementation that can be updated by a `Reconciler`.
+///
+/// `Reconciler` computes the changes needed to turn the previously rendered tree into the newly
+/// rendered one, and applies them through these methods. `web_render` uses a backend for the
+/// browser's DOM, while `mock_dom::MockDom` is an in-memory backend for testing.
+///
+/// Child indices refer to the positions of the children of `parent` that were created or
+/// claimed through the backend.
+pub trait Backend<Message: 'static> {
+    /// Handle to a node of the DOM
+    type Node;
+
+    /// Handle to a listener added with `add_listener`
+    type ListenerHandle;
+
+    /// Creates an element that is not yet attached to the DOM.
+    fn create_element(&mut self, tag: &str, namespace: Namespace) -> Self::Node;
+
+    /// Creates a text node that is not yet attached to the DOM.
+    fn create_text(&mut self, text: &str) -> Self::Node;
+
+    /// Creates a node containing trusted HTML markup that is not yet attached to the DOM.
+    fn create_raw_html(&mut self, markup: &str) -> Self::Node;
+
+    /// Replaces the text of a node created with `create_text`.
+    fn set_text(&mut self, node: &Self::Node, text: &str);
+
+    /// Inserts `child` as the child of `parent` at `index`.
+    fn insert(&mut self, parent: &Self::Node, index: usize, child: &Self::Node);
+
+    /// Moves the child of `parent` at `old_index` so that it ends up at `new_index`, shifting the
+    /// children in between.
+    fn move_child(&mut self, parent: &Self::Node, old_index: usize, new_index: usize);
+
+    /// Removes `child` and all of its descendants from `parent`.
+    ///
+    /// Listeners of the removed nodes must never be called again.
+    fn remove(&mut self, parent: &Self::Node, child: &Self::Node);
+
+    /// Sets the attribute of `node` named `name`, replacing any previous value.
+    fn set_attribute(&mut self, node: &Self::Node, name: &str, value: &AttributeValue);
+
+    /// Removes the attribute of `node` named `name`.
+    fn remove_attribute(&mut self, node: &Self::Node, name: &str);
+
+    /// Sets a DOM property of `node`, such as the `value` of an `input`.
+    ///
+    /// Unlike attributes, properties are set on every render, even if they haven't changed,
+    /// because the user can change them on the live element (for example, by typing into an
+    /// `input`). Implementations should only assign the property if its current value differs,
+    /// so that unchanged elements aren't disturbed.
+    fn set_property(&mut self, node: &Self::Node, property: &KeyValue);
+
+    /// Resets the DOM property of `node` named `name`, which is no longer rendered.
+    fn remove_property(&mut self, node: &Self::Node, name: &str);
+
+    /// Calls `listener` whenever an event of type `event_type` occurs on `node`. `keys` are the
+    /// keys of `node` and its ancestors, to be passed to the application's `Updater`.
+    ///
+    /// # Safety
+    ///
+    /// `listener` is only valid until it is removed with `remove_listener` or `node` is removed
+    /// from the DOM, and must not be used afterwards.
+    unsafe fn add_listener(
+        &mut self,
+        node: &Self::Node,
+        event_type: &'static str,
+        listener: *const dyn Listener<Message>,
+        keys: KeyIter,
+    ) -> Self::ListenerHandle;
+
+    /// Removes a listener added with `add_listener`.
+    fn remove_listener(
+        &mut self,
+        node: &Self::Node,
+        event_type: &'static str,
+        listener: Self::ListenerHandle,
+    );
+
+    /// Adopts an existing, unclaimed child of `parent` following the first `index` children if
+    /// it matches `value`. Used by `Reconciler::hydrate`.
+    ///
+    /// The default implementation never claims anything, so hydration renders from scratch.
+    fn claim_child(&mut self, _parent: &Self::Node, _index: usize, _value: DomValue)
+        -> Option<Self::Node>
+    {
+        None
+    }
+
+    /// Returns whether the attributes of `node`, an element which was just claimed, are exactly
+    /// `attributes`. Used by `Reconciler::hydrate` to report server-rendered elements whose
+    /// attributes differ from the first render.
+    ///
+    /// `attributes` are sorted by name, and include DOM properties (which are written as
+    /// attributes in HTML) but not `Bool(false)` values. The default implementation doesn't
+    /// compare anything.
+    fn claimed_attributes_match(&self, _node: &Self::Node, _attributes: &[(&str, &AttributeValue)])
+        -> bool
+    {
+        true
+    }
+
+    /// Returns the names of the attributes of `node`, an element which was just claimed. Used by
+    /// `Reconciler::hydrate` to remove server-rendered attributes that the first render doesn't
+    /// have, when `claimed_attributes_match` returns `false`.
+    ///
+    /// The default implementation returns no names, so no attributes are removed.
+    fn claimed_attribute_names(&self, _node: &Self::Node) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Removes the unclaimed children of `parent` following the first `index` children.
+    fn remove_unclaimed_children(&mut self, _parent: &Self::Node, _index: usize) {}
+
+    /// Reports that no existing child of `parent` matched `value` while hydrating, or that the
+    /// attributes of the element claimed for it differ.
+    fn hydration_mismatch(&mut self, _parent: &Self::Node, _index: usize, _value: DomValue) {}
+}
+
+/// Keeps a `Backend`'s DOM in sync with a `DomNode` that is rendered repeatedly.
+///
+/// Each call to `render` compares the new `DomNode` to the previous one, and makes the smallest
+/// set of changes it can to the backend's DOM: nodes with the same value and keys are reused,
+/// moved and updated rather than being replaced. Text nodes are reused whatever their text, and
+/// only have their text replaced when it changes. When the children of a node are reordered, the
+/// fewest possible nodes are moved, so that keyed lists of any length can be reversed, sorted or
+/// prepended to cheaply. DOM properties are set on every render, after the children of their
+/// node, so that the backend can restore any that the user has changed.
+///
+/// The `Reconciler` owns the rendered `DomNode`, so that its listeners stay valid until the next
+/// render. Listeners are matched to those of the last render by event type, and the backend is
+/// only asked to add or remove a listener when a node starts or stops listening for a type of
+/// event. Backends can also listen for each type of event once, on the root, and route events to
+/// the current render's listeners with `dispatch`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::diff::Reconciler;
+/// use domafic::mock_dom::MockDom;
+/// use domafic::on;
+/// use domafic::tags::{button, div};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Msg { Increment }
+///
+/// let render = |count: i32| div(button((on("click", |_| Msg::Increment), count.to_string())));
+///
+/// let dom = MockDom::new();
+/// let root = dom.root();
+/// let mut reconciler = Reconciler::new(dom, root);
+///
+/// reconciler.render(render(0));
+/// let button_node = {
+///     let dom = reconciler.backend();
+///     let div_node = dom.children(dom.root())[0];
+///     dom.children(div_node)[0]
+/// };
+/// let (message, _keys) = reconciler.backend().dispatch(button_node, "click").unwrap();
+/// assert_eq!(Msg::Increment, message);
+///
+/// reconciler.render(render(1));
+/// assert_eq!("<div><button>1</button></div>", reconciler.backend().to_string());
+/// ```
+pub struct Reconciler<Message: 'static, D: DomNode<Message>, B: Backend<Message>> {
+    backend: B,
+    root: B::Node,
+    children: Vec<VDomNode<Message, B>>,
+    rendered: Option<Box<D>>,
+}
+
+impl<M: 'static, D: DomNode<M>, B: Backend<M>> Reconciler<M, D, B> {
+    /// Creates a `Reconciler` that renders into `root`.
+    pub fn new(backend: B, root: B::Node) -> Reconciler<M, D, B> {
+        Reconciler { backend, root, children: Vec::new(), rendered: None }
+    }
+
+    /// Updates the children of the root to match `node`.
+    pub fn render(&mut self, node: D) {
+        self.update(node, false)
+    }
+
+    /// Renders `node` for the first time, adopting the existing children of the root (such as
+    /// server-rendered HTML) rather than creating new ones.
+    ///
+    /// Nodes are claimed using `Backend::claim_child`. Once a node can't be claimed, the rest
+    /// of its siblings are rendered from scratch. Claimed elements whose attributes differ are
+    /// reported with `Backend::hydration_mismatch`, and then updated like any other element.
+    pub fn hydrate(&mut self, node: D) {
+        self.update(node, true)
+    }
+
+    /// Returns the most recently rendered `DomNode`.
+    pub fn rendered(&self) -> Option<&D> {
+        self.rendered.as_deref()
+    }
+
+    /// Returns the backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend mutably.
+    ///
+    /// The `Reconciler` isn't aware of changes made directly to the backend's DOM, so they should
+    /// be limited to those a user could make, such as changing DOM properties.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Sends `event` to the listeners for its `type_str` on the node at `path`, and returns the
+    /// messages they produce along with the keys of the node. If `bubbles` is true, the event is
+    /// then sent to each of the node's ancestors in turn.
+    ///
+    /// `path` gives the index of each node among its siblings, starting from the children of the
+    /// root. If it leads inside a node whose contents aren't rendered by the `Reconciler`, such
+    /// as raw HTML, a bubbling event starts from that node, and other events are ignored.
+    ///
+    /// ```rust
+    /// use domafic::diff::Reconciler;
+    /// use domafic::mock_dom::MockDom;
+    /// use domafic::{DomNode, Event, on};
+    /// use domafic::tags::{button, div};
+    ///
+    /// let dom = MockDom::new();
+    /// let root = dom.root();
+    /// let mut reconciler = Reconciler::new(dom, root);
+    /// reconciler.render(div((
+    ///     on("click", |_| "div"),
+    ///     button((on("click", |_| "button"), "Click me")).with_key(3),
+    /// )));
+    ///
+    /// let event = Event {
+    ///     type_str: Some("click"),
+    ///     target_value: None,
+    ///     client_x: 0,
+    ///     client_y: 0,
+    ///     offset_x: 0,
+    ///     offset_y: 0,
+    ///     which_keycode: 0,
+    ///     shift_key: false,
+    ///     alt_key: false,
+    ///     ctrl_key: false,
+    ///     meta_key: false,
+    /// };
+    /// let messages = reconciler.dispatch(&[0, 0], event, true);
+    /// let messages = messages.into_iter()
+    ///     .map(|(message, keys)| (message, keys.collect::<Vec<_>>()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vec![("button", vec![3]), ("div", vec![])], messages);
+    /// ```
+    pub fn dispatch(&self, path: &[usize], event: Event, bubbles: bool) -> Vec<(M, KeyIter)> {
+        let event_type = match event.type_str {
+            Some(event_type) => event_type,
+            None => return Vec::new(),
+        };
+
+        let mut nodes = Vec::new();
+        let mut children = &self.children;
+        for &index in path {
+            match children.get(index) {
+                Some(vnode) => {
+                    nodes.push(vnode);
+                    children = &vnode.children;
+                },
+                None => break,
+            }
+        }
+        if !bubbles {
+            // Only the node at the end of the path is sent the event
+            if nodes.len() < path.len() {
+                return Vec::new();
+            }
+            nodes = nodes.pop().into_iter().collect();
+        }
+
+        let mut messages = Vec::new();
+        for vnode in nodes.into_iter().rev() {
+            for (_, slot) in &vnode.listeners {
+                if slot.event_type == event_type {
+                    messages.push((slot.handle_event(event), vnode.keys.into_iter()));
+                }
+            }
+        }
+        messages
+    }
+
+    fn update(&mut self, node: D, hydrating: bool) {
+        // The listener slots refer to the old node's listeners until the diff below points them
+        // at the new node's, so events must not be dispatched in between
+        match self.rendered {
+            Some(ref mut rendered) => **rendered = node,
+            None => self.rendered = Some(Box::new(node)),
+        }
+        let rendered = self.rendered.as_ref().unwrap();
+
+        let old_children = mem::take(&mut self.children);
+        let mut acc = DiffAcc::new(&mut self.backend, Keys::new(), &self.root, old_children,
+            hydrating);
+        match rendered.process_all::<Differ<M, B>>(&mut acc) {
+            Ok(()) => {},
+            Err(never) => match never {},
+        }
+        self.children = acc.finish();
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum VNodeValue {
+    Text(String),
+    Tag(String, Namespace),
+    RawHtml(String),
+}
+impl VNodeValue {
+    fn new(value: DomValue) -> VNodeValue {
+        match value {
+            DomValue::Element { tag, namespace } =>
+                VNodeValue::Tag(tag.to_string(), namespace),
+            DomValue::Text(text) => VNodeValue::Text(text.to_string()),
+            DomValue::RawHtml(markup) => VNodeValue::RawHtml(markup.to_string()),
+            DomValue::FlushPoint => unreachable!("Flush points are never rendered"),
+        }
+    }
+
+    // Compares without allocating so that matching existing nodes is cheap
+    fn matches(&self, value: DomValue) -> bool {
+        match (self, value) {
+            (VNodeValue::Tag(old_tag, old_namespace), DomValue::Element { tag, namespace }) =>
+                old_tag == tag && *old_namespace == namespace,
+            // Text nodes match regardless of their text, which is updated in place
+            (VNodeValue::Text(_), DomValue::Text(_)) => true,
+            // Raw HTML is only replaced when the markup changes
+            (VNodeValue::RawHtml(old), DomValue::RawHtml(markup)) => old == markup,
+            _ => false,
+        }
+    }
+}
+
+struct VDomNode<M: 'static, B: Backend<M>> {
+    value: VNodeValue,
+    keys: Keys,
+    node: B::Node,
+    // Sorted by name, without repeated names
+    attributes: Vec<(String, AttributeValue)>,
+    // Sorted by name, without repeated names
+    properties: Vec<KeyValue>,
+    listeners: Vec<(B::ListenerHandle, Box<ListenerSlot<M>>)>,
+    children: Vec<VDomNode<M, B>>,
+}
+
+// The listener given to the backend in place of each listener of the rendered `DomNode`. The
+// slot is boxed so that its address is stable, and is pointed at the matching listener of each
+// new render, so that the backend's listener doesn't need to be replaced.
+struct ListenerSlot<M: 'static> {
+    event_type: &'static str,
+    listener: Cell<*const dyn Listener<M>>,
+}
+
+impl<M: 'static> Listener<M> for ListenerSlot<M> {
+    fn event_type_handled(&self) -> &'static str {
+        self.event_type
+    }
+
+    fn handle_event(&self, event: Event) -> M {
+        // Safe because the slot always points to a listener of the `DomNode` owned by the
+        // `Reconciler`
+        unsafe { (*self.listener.get()).handle_event(event) }
+    }
+}
+
+// Adds `listener` to `dom_node` through a new `ListenerSlot`
+fn add_listener<M: 'static, B: Backend<M>>(backend: &mut B, dom_node: &B::Node,
+    listener: *const dyn Listener<M>, keys: Keys) -> (B::ListenerHandle, Box<ListenerSlot<M>>)
+{
+    let event_type = unsafe { (*listener).event_type_handled() };
+    let slot = Box::new(ListenerSlot { event_type, listener: Cell::new(listener) });
+    // Safe because the slot is owned by the node's `VDomNode`, which removes the listener before
+    // dropping it
+    let handle = unsafe {
+        backend.add_listener(dom_node, event_type, &*slot as &dyn Listener<M>, keys.into_iter())
+    };
+    (handle, slot)
+}
+
+// Where a node of the current render came from
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Source {
+    // Reused from the last render, at this index among its siblings
+    Old(usize),
+    // Adopted while hydrating
+    Claimed,
+    // Newly created, and not yet attached to the DOM
+    Created,
+}
+
+struct Differ<'n, M, B>(PhantomData<(&'n (), M, B)>);
+struct DiffAcc<'n, M: 'static, B: Backend<M> + 'n> where B::Node: 'n {
+    backend: &'n mut B,
+    keys: Keys,
+    parent: &'n B::Node,
+    // Children of `parent` from the last render, taken as they are reused
+    old: Vec<Option<VDomNode<M, B>>>,
+    // Indices into `old` grouped by keys, built the first time a node isn't found in place.
+    // Entries before the first index of each group have already been taken.
+    old_by_keys: Option<HashMap<Keys, (usize, Vec<usize>)>>,
+    // Children of `parent` for the current render, in order
+    new: Vec<(VDomNode<M, B>, Source)>,
+    // Whether existing nodes are being adopted
+    hydrating: bool,
+}
+
+impl<'n, M: 'static, B: Backend<M> + 'n> DiffAcc<'n, M, B> where B::Node: 'n {
+    fn new(backend: &'n mut B, keys: Keys, parent: &'n B::Node, old: Vec<VDomNode<M, B>>,
+        hydrating: bool) -> DiffAcc<'n, M, B>
+    {
+        DiffAcc {
+            backend,
+            keys,
+            parent,
+            new: Vec::with_capacity(old.len()),
+            old: old.into_iter().map(Some).collect(),
+            old_by_keys: None,
+            hydrating,
+        }
+    }
+
+    // Takes the first unused node from the last render with equal keys and a matching value
+    fn take_match(&mut self, keys: &Keys, value: DomValue) -> Option<(usize, VDomNode<M, B>)> {
+        // Unchanged nodes are found in place without building `old_by_keys`
+        let index = self.new.len();
+        let in_place = match self.old.get(index) {
+            Some(Some(vnode)) => vnode.keys == *keys && vnode.value.matches(value),
+            _ => false,
+        };
+        if in_place {
+            return self.old[index].take().map(|vnode| (index, vnode));
+        }
+
+        let old = &mut self.old;
+        let old_by_keys = self.old_by_keys.get_or_insert_with(|| {
+            let mut old_by_keys = HashMap::new();
+            for (index, vnode) in old.iter().enumerate() {
+                if let Some(ref vnode) = *vnode {
+                    old_by_keys.entry(vnode.keys).or_insert_with(|| (0, Vec::new())).1.push(index);
+                }
+            }
+            old_by_keys
+        });
+
+        let (ref mut first, ref indices) = *old_by_keys.get_mut(keys)?;
+        while *first < indices.len() && old[indices[*first]].is_none() {
+            *first += 1;
+        }
+        let index = indices[*first..].iter().cloned().find(|&index| match old[index] {
+            Some(ref vnode) => vnode.value.matches(value),
+            None => false,
+        })?;
+        old[index].take().map(|vnode| (index, vnode))
+    }
+
+    // Removes the nodes from the last render that weren't reused, then moves and inserts nodes
+    // so that the children of `parent` are in the order they were rendered
+    fn finish(self) -> Vec<VDomNode<M, B>> {
+        let DiffAcc { backend, parent, old, new, hydrating, .. } = self;
+        if hydrating {
+            backend.remove_unclaimed_children(parent, new.len());
+        }
+
+        // Index of each reused node among the nodes that are left
+        let mut old_positions = Vec::with_capacity(old.len());
+        let mut reused = 0;
+        for vnode in old {
+            old_positions.push(reused);
+            match vnode {
+                Some(unused) => backend.remove(parent, &unused.node),
+                None => reused += 1,
+            }
+        }
+
+        // Claimed nodes are adopted in order, before any others are attached
+        let claimed = new.iter().filter(|&&(_, source)| source == Source::Claimed).count();
+        let mut claimed_positions = 0..claimed;
+        let (nodes, positions): (Vec<_>, Vec<_>) = new.into_iter().map(|(vnode, source)| {
+            let position = match source {
+                Source::Old(index) => Some(claimed + old_positions[index]),
+                Source::Claimed => claimed_positions.next(),
+                Source::Created => None,
+            };
+            (vnode, position)
+        }).unzip();
+
+        reorder(backend, parent, &nodes, &positions);
+        nodes
+    }
+}
+
+impl<'a, 'n, M, B> DomNodeProcessor<'a, M> for Differ<'n, M, B>
+    where M: 'static, B: Backend<M> + 'n, B::Node: 'n
+{
+    type Acc = DiffAcc<'n, M, B>;
+    type Error = Infallible;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn add_node<'a, 'n, T, M, B>(acc: &mut DiffAcc<'n, M, B>, node: &'a T)
+            -> Result<(), Infallible>
+            where T: DomNode<M>, M: 'static, B: Backend<M> + 'n, B::Node: 'n
+        {
+            let node_value = node.value();
+            if node_value == DomValue::FlushPoint {
+                // Flush points only matter when streaming HTML, and have no DOM node
+                return Ok(());
+            }
+
+            let keys = if let Some(new_key) = node.key() {
+                acc.keys.push(new_key)
+            } else {
+                acc.keys
+            };
+
+            let listeners = {
+                let mut listeners = Vec::new();
+                node.listeners().process_all::<ListenersToVec<M>>(&mut listeners)?;
+                listeners
+            };
+
+            // Match iff "keys" and "value" are equal
+            if let Some((old_index, mut vnode)) = acc.take_match(&keys, node_value) {
+                // Modify the existing node
+                // Update text, add new listeners, unify attributes, unify children
+                let backend = &mut *acc.backend;
+
+                if let (&mut VNodeValue::Text(ref mut old_text), DomValue::Text(text)) =
+                    (&mut vnode.value, node_value)
+                {
+                    if old_text != text {
+                        backend.set_text(&vnode.node, text);
+                        old_text.clear();
+                        old_text.push_str(text);
+                    }
+                }
+
+                // Unify listeners by event type. Each new listener takes over the first unused
+                // slot with its type, so listeners are only added for new types, and removed for
+                // types that are no longer listened for.
+                let mut old_listeners = mem::take(&mut vnode.listeners);
+                for listener in listeners {
+                    let event_type = unsafe { (*listener).event_type_handled() };
+                    match old_listeners.iter().position(|x| x.1.event_type == event_type) {
+                        Some(index) => {
+                            let old_listener = old_listeners.remove(index);
+                            old_listener.1.listener.set(listener);
+                            vnode.listeners.push(old_listener);
+                        },
+                        None => vnode.listeners.push(
+                            add_listener(backend, &vnode.node, listener, keys)),
+                    }
+                }
+                for (handle, slot) in old_listeners {
+                    backend.remove_listener(&vnode.node, slot.event_type, handle);
+                }
+
+                // Unify attributes by name. Both lists are sorted by name, so they can be
+                // walked together: names only in the old list are removed, and new or changed
+                // values are set.
+                let mut old_attributes = mem::take(&mut vnode.attributes).into_iter().peekable();
+                for (name, value) in by_name(node.attributes()) {
+                    let mut old_value = None;
+                    while let Some(old_attribute) =
+                        old_attributes.next_if(|old_attribute| old_attribute.0.as_str() <= name)
+                    {
+                        if old_attribute.0 == name {
+                            old_value = Some(old_attribute);
+                        } else {
+                            backend.remove_attribute(&vnode.node, &old_attribute.0);
+                        }
+                    }
+                    match old_value {
+                        // Unchanged attributes are kept without copying them again
+                        Some(old_attribute) if old_attribute.1 == *value =>
+                            vnode.attributes.push(old_attribute),
+                        _ => {
+                            backend.set_attribute(&vnode.node, name, value);
+                            vnode.attributes.push((name.to_string(), value.clone()));
+                        }
+                    }
+                }
+                for old_attribute in old_attributes {
+                    backend.remove_attribute(&vnode.node, &old_attribute.0);
+                }
+
+                // To the children! The node is moved into place by `DiffAcc::finish`
+                let children = {
+                    let old_children = mem::take(&mut vnode.children);
+                    let mut child_acc =
+                        DiffAcc::new(backend, keys, &vnode.node, old_children, false);
+                    node.children().process_all::<Differ<M, B>>(&mut child_acc)?;
+                    child_acc.finish()
+                };
+                vnode.children = children;
+
+                // Properties are set after the children, so that the `value` of a `select` can
+                // refer to its `option`s
+                let old_properties = mem::take(&mut vnode.properties);
+                vnode.properties = set_properties(backend, &vnode.node, old_properties, node);
+                acc.new.push((vnode, Source::Old(old_index)));
+            } else {
+                // Adopt the existing node if hydrating, or construct a new one
+                let index = acc.new.len();
+                let claimed_node = if acc.hydrating {
+                    let claimed_node = acc.backend.claim_child(acc.parent, index, node_value);
+                    let is_element = matches!(node_value, DomValue::Element { .. });
+                    match claimed_node {
+                        Some(ref claimed_node) if is_element => {
+                            // Compare the attributes as they are written to HTML
+                            let mut attributes = by_name(node.attributes().chain(
+                                node.properties().map(|&(name, ref value)| (name, value))
+                            ));
+                            attributes.retain(|&(_, value)| *value != AttributeValue::Bool(false));
+                            if !acc.backend.claimed_attributes_match(claimed_node, &attributes) {
+                                acc.backend.hydration_mismatch(acc.parent, index, node_value);
+                                // Rendered attributes are set below, but the rest must be removed
+                                for name in acc.backend.claimed_attribute_names(claimed_node) {
+                                    if attributes.iter().all(|&(rendered, _)| rendered != name) {
+                                        acc.backend.remove_attribute(claimed_node, &name);
+                                    }
+                                }
+                            }
+                        }
+                        Some(_) => {},
+                        None => {
+                            // Render the rest of this level from scratch
+                            acc.backend.hydration_mismatch(acc.parent, index, node_value);
+                            acc.backend.remove_unclaimed_children(acc.parent, index);
+                            acc.hydrating = false;
+                        }
+                    }
+                    claimed_node
+                } else {
+                    None
+                };
+                let is_claimed = claimed_node.is_some();
+
+                let backend = &mut *acc.backend;
+                let dom_node = match (claimed_node, node_value) {
+                    (Some(claimed_node), _) => claimed_node,
+                    (None, DomValue::Element { tag, namespace }) =>
+                        backend.create_element(tag, namespace),
+                    (None, DomValue::Text(text)) => backend.create_text(text),
+                    (None, DomValue::RawHtml(markup)) => backend.create_raw_html(markup),
+                    (None, DomValue::FlushPoint) => unreachable!("Flush points are never rendered"),
+                };
+
+                let listeners = listeners.into_iter()
+                    .map(|listener| add_listener(backend, &dom_node, listener, keys))
+                    .collect();
+
+                let mut vnode_attributes = Vec::new();
+                for (name, value) in by_name(node.attributes()) {
+                    backend.set_attribute(&dom_node, name, value);
+                    vnode_attributes.push((name.to_string(), value.clone()));
+                }
+
+                let mut vnode = VDomNode {
+                    value: VNodeValue::new(node_value),
+                    keys,
+                    node: dom_node,
+                    attributes: vnode_attributes,
+                    properties: Vec::new(),
+                    listeners,
+                    children: Vec::new(),
+                };
+
+                // Created nodes are attached by `DiffAcc::finish`
+                let children = {
+                    let mut child_acc =
+                        DiffAcc::new(backend, keys, &vnode.node, Vec::new(), is_claimed);
+                    node.children().process_all::<Differ<M, B>>(&mut child_acc)?;
+                    child_acc.finish()
+                };
+                vnode.children = children;
+                vnode.properties = set_properties(backend, &vnode.node, Vec::new(), node);
+                acc.new.push((vnode, if is_claimed { Source::Claimed } else { Source::Created }));
+            }
+
+            Ok(())
+        }
+        add_node
+    }
+}
+
+// Attributes and properties, which are compared by name
+trait Named {
+    fn name(&self) -> &str;
+}
+impl<'a> Named for (&'a str, &'a AttributeValue) {
+    fn name(&self) -> &str { self.0 }
+}
+impl Named for &KeyValue {
+    fn name(&self) -> &str { self.0 }
+}
+
+// Returns `attributes` sorted by name. When a name is repeated, only the last attribute with that
+// name is kept, as when writing HTML.
+fn by_name<T: Named, I: Iterator<Item = T>>(attributes: I) -> Vec<T> {
+    let mut attributes = attributes.collect::<Vec<_>>();
+    // The sort is stable, so reversing first puts the last attribute with each name first
+    attributes.reverse();
+    attributes.sort_by(|a, b| a.name().cmp(b.name()));
+    attributes.dedup_by(|a, b| a.name() == b.name());
+    attributes
+}
+
+// Sets all of the properties of `node` on `dom_node`, and removes those of `old` which are no
+// longer rendered. Returns the new properties, sorted by name.
+fn set_properties<M, B, T>(backend: &mut B, dom_node: &B::Node, old: Vec<KeyValue>, node: &T)
+    -> Vec<KeyValue>
+    where M: 'static, B: Backend<M>, T: DomNode<M>
+{
+    let properties = by_name(node.properties());
+    for old_property in old {
+        if properties.binary_search_by_key(&old_property.0, |property| property.0).is_err() {
+            backend.remove_property(dom_node, old_property.0);
+        }
+    }
+    properties.into_iter().map(|property| {
+        backend.set_property(dom_node, property);
+        property.clone()
+    }).collect()
+}
+
+// Moves and inserts the children of `parent` so that they are in the order of `nodes`.
+// `positions` are the current indices of the nodes among the children of `parent`, or `None` for
+// nodes that aren't attached yet.
+//
+// Attached nodes whose positions form a longest increasing subsequence are already in order
+// relative to each other, so they stay where they are and every other node is moved (or
+// inserted) exactly once. This is the smallest possible number of moves.
+fn reorder<M, B>(backend: &mut B, parent: &B::Node, nodes: &[VDomNode<M, B>],
+    positions: &[Option<usize>])
+    where M: 'static, B: Backend<M>
+{
+    if positions.iter().enumerate().all(|(index, &position)| position == Some(index)) {
+        return;
+    }
+
+    let stable = longest_increasing_subsequence(positions);
+
+    // Number of stable nodes before each current position
+    let attached = positions.iter().filter(|position| position.is_some()).count();
+    let mut is_stable_at = vec![false; attached];
+    for (&position, &is_stable) in positions.iter().zip(&stable) {
+        if let Some(position) = position {
+            is_stable_at[position] = is_stable;
+        }
+    }
+    let mut stable_before = Vec::with_capacity(attached);
+    let mut count = 0;
+    for is_stable in is_stable_at {
+        stable_before.push(count);
+        if is_stable {
+            count += 1;
+        }
+    }
+
+    // While nodes are being moved, the children of `parent` are always ordered by these keys:
+    // (number of stable nodes before the node, phase, index). Between two stable nodes are
+    // first the nodes that still have to be moved, in their current order (phase 0), then the
+    // nodes that have already been moved or inserted, in their new order (phase 1). This lets
+    // the index of a node be found by counting the keys before its own.
+    let mut current_keys = Vec::with_capacity(nodes.len());
+    let mut new_keys = Vec::with_capacity(nodes.len());
+    let mut stable_count = 0;
+    for (index, (&position, &is_stable)) in positions.iter().zip(&stable).enumerate() {
+        if is_stable {
+            current_keys.push(Some((stable_count, 2, 0)));
+            new_keys.push(None);
+            stable_count += 1;
+        } else {
+            current_keys.push(position.map(|position| (stable_before[position], 0, position)));
+            new_keys.push(Some((stable_count, 1, index)));
+        }
+    }
+    let mut sorted_keys = current_keys.iter().chain(&new_keys).filter_map(|&key| key)
+        .collect::<Vec<_>>();
+    sorted_keys.sort();
+    let rank = |key| sorted_keys.binary_search(&key).unwrap();
+
+    let mut present = FenwickTree::new(sorted_keys.len());
+    for &key in current_keys.iter().flatten() {
+        present.insert(rank(key));
+    }
+
+    for (index, vnode) in nodes.iter().enumerate() {
+        let new_rank = match new_keys[index] {
+            Some(key) => rank(key),
+            None => continue,
+        };
+        match current_keys[index] {
+            Some(key) => {
+                let current_rank = rank(key);
+                let from = present.count_before(current_rank);
+                present.remove(current_rank);
+                let to = present.count_before(new_rank);
+                if from != to {
+                    backend.move_child(parent, from, to);
+                }
+            }
+            None => backend.insert(parent, present.count_before(new_rank), &vnode.node),
+        }
+        present.insert(new_rank);
+    }
+}
+
+// Marks the attached nodes whose positions form a longest increasing subsequence
+fn longest_increasing_subsequence(positions: &[Option<usize>]) -> Vec<bool> {
+    // `tails[k]` is the node that ends the increasing subsequence of length `k + 1` with the
+    // lowest final position found so far
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; positions.len()];
+    for (index, &position) in positions.iter().enumerate() {
+        let position = match position {
+            Some(position) => position,
+            None => continue,
+        };
+        let length = match tails.binary_search_by_key(&position, |&tail| positions[tail].unwrap()) {
+            Ok(length) | Err(length) => length,
+        };
+        if length > 0 {
+            previous[index] = Some(tails[length - 1]);
+        }
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+
+    let mut in_subsequence = vec![false; positions.len()];
+    let mut next = tails.last().cloned();
+    while let Some(index) = next {
+        in_subsequence[index] = true;
+        next = previous[index];
+    }
+    in_subsequence
+}
+
+// Counts the members of a set of indices that are less than a given index
+struct FenwickTree(Vec<usize>);
+impl FenwickTree {
+    fn new(len: usize) -> FenwickTree {
+        FenwickTree(vec![0; len + 1])
+    }
+
+    fn insert(&mut self, index: usize) {
+        let mut i = index + 1;
+        while i < self.0.len() {
+            self.0[i] += 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        let mut i = index + 1;
+        while i < self.0.len() {
+            self.0[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn count_before(&self, index: usize) -> usize {
+        let mut count = 0;
+        let mut i = index;
+        while i > 0 {
+            count += self.0[i];
+            i -= i & i.wrapping_neg();
+        }
+        count
+    }
+}
+
+struct ListenersToVec<Message: 'static>(PhantomData<Message>);
+impl<'a, M: 'static> ListenerProcessor<'a, M> for ListenersToVec<M> {
+    type Acc = Vec<*const dyn Listener<M>>;
+    type Error = Infallible;
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn add_listener_to_vec<M, L: Listener<M>>(
+            vec: &mut Vec<*const dyn Listener<M>>,
+            listener: &L) -> Result<(), Infallible>
+        {
+            vec.push(
+                // Extend the lifetime of the listener (we know it's valid until at least the
+                // next render) and convert it to a *const
+                unsafe { mem::transmute::<&dyn Listener<M>, *const dyn Listener<M>>(listener) }
+            );
+            Ok(())
+        }
+        add_listener_to_vec
+    }
+}
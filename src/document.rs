@@ -0,0 +1,131 @@
+use DomNode;
+use html_writer::{DocumentParts, FlushPolicy, HtmlFormat};
+#[cfg(feature = "use_serde")]
+use state::{STATE_SCRIPT_ID, to_script_json};
+#[cfg(feature = "use_serde")]
+extern crate serde;
+#[cfg(feature = "use_serde")]
+extern crate serde_json;
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::marker::PhantomData;
+use std::{fmt, io};
+
+/// A complete HTML5 document, including the doctype, `html`, `head` and `body` elements.
+///
+/// `title`, `meta`, `link` and `script` elements found anywhere in the body's tree are moved into
+/// the document's head, along with the contents of any `head` elements, so components anywhere in
+/// the tree can declare titles, `meta` tags, stylesheets and scripts. When more than one `title`
+/// is declared, only the last one is written.
+///
+/// If the body node is not itself a `body` element, it is wrapped in one.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::AttributeValue::Str;
+/// use domafic::document::Document;
+/// use domafic::tags::{attributes, base, div, h1, head, link, title};
+/// use std::marker::PhantomData;
+///
+/// let profile = div((
+///     title("Profile"),
+///     h1("Hello!"),
+///     link(attributes([("rel", Str("stylesheet")), ("href", Str("/profile.css"))])),
+///     head(base(attributes([("href", Str("/users/"))]))),
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+///
+/// let document = Document::new(profile).with_title("Untitled").with_lang("en");
+/// assert_eq!(
+///     "<!DOCTYPE html><html lang=\"en\"><head><title>Profile</title>\
+///     <link rel=\"stylesheet\" href=\"/profile.css\"><base href=\"/users/\"></head>\
+///     <body><div><h1>Hello&#33;</h1></div></body></html>",
+///     document.to_string()
+/// );
+/// ```
+pub struct Document<Message, Body: DomNode<Message>> {
+    body: Body,
+    title: Option<String>,
+    lang: Option<String>,
+    // JSON serialized application state, already escaped for use in a `script`
+    #[cfg(feature = "use_serde")]
+    state: Option<String>,
+    format: HtmlFormat,
+    msg_marker: PhantomData<Message>,
+}
+
+impl<M, T: DomNode<M>> Document<M, T> {
+    /// Creates a document with the given body.
+    pub fn new(body: T) -> Document<M, T> {
+        Document {
+            body,
+            title: None,
+            lang: None,
+            #[cfg(feature = "use_serde")]
+            state: None,
+            format: HtmlFormat::new(),
+            msg_marker: PhantomData,
+        }
+    }
+
+    /// Sets the title to use if no `title` elements are declared in the body's tree.
+    pub fn with_title<S: Into<String>>(self, title: S) -> Document<M, T> {
+        Document { title: Some(title.into()), ..self }
+    }
+
+    /// Sets the `lang` attribute of the `html` element.
+    pub fn with_lang<S: Into<String>>(self, lang: S) -> Document<M, T> {
+        Document { lang: Some(lang.into()), ..self }
+    }
+
+    /// Embeds `state` in the document's head as JSON so that the client can start from the
+    /// same state that produced the document. See `state::state_script` for details.
+    #[cfg(feature = "use_serde")]
+    pub fn with_state<S: serde::Serialize>(self, state: &S)
+        -> Result<Document<M, T>, serde_json::Error>
+    {
+        Ok(Document { state: Some(to_script_json(state)?), ..self })
+    }
+
+    /// Sets the format used to write the document.
+    pub fn with_format(self, format: HtmlFormat) -> Document<M, T> {
+        Document { format, ..self }
+    }
+
+    /// Writes the document to `writer`.
+    pub fn write_html<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.parts().write_html(&self.body, self.format, writer)
+    }
+
+    /// Writes the document to a `fmt::Write`, such as a `String`.
+    pub fn write_html_fmt<W: fmt::Write>(&self, writer: &mut W) -> fmt::Result {
+        self.parts().write_html_fmt(&self.body, self.format, writer)
+    }
+
+    /// Streams the document to `writer`, flushing at the boundaries chosen by `flush`.
+    ///
+    /// With `FlushPolicy::new()`, the doctype and head are sent before the body is written.
+    /// See `HtmlFormat::stream_html` for details.
+    pub fn stream_html<W: io::Write>(&self, writer: &mut W, flush: FlushPolicy) -> io::Result<()> {
+        self.parts().stream_html(&self.body, self.format, writer, flush)
+    }
+
+    fn parts(&self) -> DocumentParts<'_> {
+        DocumentParts {
+            lang: self.lang.as_deref(),
+            title: self.title.as_deref(),
+            #[cfg(feature = "use_serde")]
+            json_script: self.state.as_deref().map(|state| (STATE_SCRIPT_ID, state)),
+            #[cfg(not(feature = "use_serde"))]
+            json_script: None,
+        }
+    }
+}
+
+impl<M, T: DomNode<M>> fmt::Display for Document<M, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.write_html_fmt(formatter)
+    }
+}
@@ -1,5 +1,6 @@
-use processors::{DomNodes, DomNodeProcessor, Listeners, EmptyListeners};
-use KeyValue;
+use processors::{DomNodes, DomNodeProcessor, Listeners, EmptyListeners, MapNodes};
+use listener::{MapListeners, MapperMarker, check_stateless_mapper};
+use {AttributeValue, KeyValue};
 
 use opt_std::marker::PhantomData;
 
@@ -53,7 +54,7 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
     /// use domafic::tags::div;
     /// use domafic::listener::on;
     ///
-    /// #[cfg(target_os = "emscripten")]
+    /// #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     /// use domafic::web_render::run;
     ///
     /// struct Clicked;
@@ -71,7 +72,7 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
     ///    println!("div number {} was clicked", keys.next().unwrap());
     ///
     /// // If using in a browser:
-    /// #[cfg(target_os = "emscripten")]
+    /// #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
     /// run("body", _update, _render, ());
     /// ```
     fn with_key(self, key: usize) -> WithKey<Message, Self> {
@@ -85,13 +86,16 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         ::html_writer::HtmlDisplayable(self, PhantomData)
     }
 
-    /// Get the nth attribute for a given `DomNode`.
+    /// Get the name and value of the nth attribute for a given `DomNode`.
     ///
-    /// If `node.get_attribute(i)` returns `None`, `node.get_attribute(j)` should return `None`
-    /// for all `j >= i`.
-    fn get_attribute(&self, _index: usize) -> Option<&KeyValue>;
+    /// Attribute names are borrowed from the node, so that nodes such as parsed HTML can own
+    /// them. If `node.get_attribute(i)` returns `None`, `node.get_attribute(j)` should return
+    /// `None` for all `j >= i`.
+    fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)>;
 
     /// Returns an iterator over a `DomNode`'s attributes.
+    ///
+    /// If more than one attribute has the same name, the last one is used when rendering.
     fn attributes(&self) -> AttributeIter<Message, Self> {
         AttributeIter { node: self, index: 0, _marker: PhantomData }
     }
@@ -113,12 +117,57 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
     ///
     /// let my_div_with_attrs = my_div.with_attributes([("key", Str("value"))]);
     ///
-    /// assert_eq!(my_div_with_attrs.get_attribute(0), Some(&("key", Str("value"))));
+    /// assert_eq!(my_div_with_attrs.get_attribute(0), Some(("key", &Str("value"))));
     ///```
     fn with_attributes<A: AsRef<[KeyValue]>>(self, attributes: A) -> WithAttributes<Message, Self, A> {
         WithAttributes { node: self, attributes: attributes, _marker: PhantomData }
     }
 
+    /// Get the nth DOM property for a given `DomNode`.
+    ///
+    /// If `node.get_property(i)` returns `None`, `node.get_property(j)` should return `None`
+    /// for all `j >= i`. Nodes have no properties unless they are added with `with_properties`.
+    fn get_property(&self, _index: usize) -> Option<&KeyValue> {
+        None
+    }
+
+    /// Returns an iterator over a `DomNode`'s DOM properties.
+    ///
+    /// If more than one property has the same name, the last one is used when rendering.
+    fn properties(&self) -> PropertyIter<'_, Message, Self> {
+        PropertyIter { node: self, index: 0, _marker: PhantomData }
+    }
+
+    /// Wrap the `DomNode` in a set of DOM properties.
+    ///
+    /// Unlike attributes, properties reflect the live state of an element, which the user can
+    /// change. Use them for the state of form controls, such as the `value` of an `input` or
+    /// whether a checkbox is `checked`: when rendering in the browser, properties are assigned
+    /// to the element directly, and are assigned again on every render if the user has changed
+    /// them. This keeps the element in sync with the application's state.
+    ///
+    /// When writing HTML, properties are written as attributes of the same name, which sets the
+    /// initial state of the element. A property takes precedence over an attribute with the
+    /// same name.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::tags::{attributes, input};
+    /// use domafic::AttributeValue::{Bool, Str};
+    /// use std::marker::PhantomData;
+    ///
+    /// let checkbox = input((attributes([("type", Str("checkbox"))]), PhantomData::<()>))
+    ///     .with_properties([("checked", Bool(true))]);
+    ///
+    /// assert_eq!(checkbox.get_property(0), Some(&("checked", Bool(true))));
+    /// assert_eq!("<input type=\"checkbox\" checked>", checkbox.displayable().to_string());
+    ///```
+    fn with_properties<P: AsRef<[KeyValue]>>(self, properties: P) -> WithProperties<Message, Self, P> {
+        WithProperties { node: self, properties, _marker: PhantomData }
+    }
+
     /// Wrap the `DomNode` in an additional set of liseners.
     ///
     /// Example:
@@ -145,9 +194,48 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         }
     }
 
-    // TODO once type ATCs land
-    // type Mapped<Mapper: Map<In=Self::Message>>: DomNode<Message=Mapper::Out>
-    // fn map_listeners<Mapper: Map<In=Self::Message>>(self) -> Mapped<Mapper>
+    /// Wrap the `DomNode` so that every message published by listeners in its subtree is
+    /// converted using function `f`.
+    ///
+    /// This allows a component to be written in terms of its own message type and embedded in
+    /// views with a different message type. `f` must not capture any state: it may be a function
+    /// (such as an enum variant constructor) or a non-capturing closure. This allows the tree to
+    /// be processed in place without storing a copy of `f` alongside every listener.
+    ///
+    /// Passing a function that captures state fails to compile:
+    ///
+    ///```rust,compile_fail
+    /// use domafic::DomNode;
+    /// use domafic::listener::on;
+    /// use domafic::tags::div;
+    ///
+    /// let offset = 5;
+    /// let _node = div(on("click", |_| 1)).map_messages(move |msg: i32| msg + offset);
+    ///```
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::listener::on;
+    /// use domafic::tags::{button, div};
+    ///
+    /// enum CounterMsg { Increment }
+    /// enum AppMsg { Counter(CounterMsg), Reset }
+    ///
+    /// let counter = button((on("click", |_| CounterMsg::Increment), "+"));
+    ///
+    /// let _app = div((
+    ///     counter.map_messages(AppMsg::Counter),
+    ///     button((on("click", |_| AppMsg::Reset), "Reset")),
+    /// ));
+    ///```
+    fn map_messages<N, F>(self, f: F) -> MapMessages<Message, N, Self, F>
+        where F: Fn(Message) -> N + Copy
+    {
+        check_stateless_mapper(&f);
+        MapMessages(PhantomData, self)
+    }
 
     /// Returns a reference to the children of this `DomNode`
     fn children(&self) -> &Self::Children;
@@ -173,19 +261,76 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         use html_writer::HtmlWriter;
         self.process_all::<HtmlWriter<W>>(writer)
     }
+
+    /// Writes the `DomNode`'s HTML representation to a `fmt::Write`, such as a `String`.
+    ///
+    /// Unlike `write_html`, no intermediate byte buffer is needed to produce a `String`.
+    #[cfg(any(feature = "use_std", test))]
+    fn write_html_fmt<W: ::std::fmt::Write>(&self, writer: &mut W) -> ::std::fmt::Result {
+        use html_writer::HtmlFmtWriter;
+        self.process_all::<HtmlFmtWriter<W>>(writer)
+    }
+
+    /// Erases the type of the `DomNode` by moving it into a `BoxedNode`.
+    ///
+    /// This allows for recursive views and for collections of differently-typed nodes.
+    /// See `boxed::BoxedNode` for more details.
+    #[cfg(any(feature = "use_std", test))]
+    fn boxed(self) -> ::boxed::BoxedNode<Message> where Self: 'static, Message: 'static {
+        ::boxed::BoxedNode::new(self)
+    }
 }
 
 /// "Value" of a `DomNode`: either an element's tag name (e.g. "div"/"h1"/"body") or the text
 /// value of a text node (e.g. "Hello world!").
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub enum DomValue<'a> {
     /// A tag element
     Element {
-        /// `&'static str` tag name, such as `div` or `span`.
-        tag: &'static str
+        /// Tag name, such as `div`, `span`, or a custom element name like `my-date-picker`.
+        tag: &'a str,
+
+        /// Namespace of the element, such as `Html` for a `div` or `Svg` for a `circle`.
+        namespace: Namespace,
     },
 
     /// A text node
     Text(&'a str),
+
+    /// Trusted markup that is inserted into the document verbatim, without escaping.
+    /// See `raw_html` for details.
+    RawHtml(&'a str),
+
+    /// A point at which streamed HTML is flushed. Flush points produce no markup, and are skipped
+    /// when rendering to the DOM. See `flush_point` for details.
+    FlushPoint,
+}
+
+/// Namespace of an element.
+///
+/// Most elements are HTML elements, but elements such as `svg` and `math` (and their children)
+/// belong to separate namespaces and must be created accordingly in the browser.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Namespace {
+    /// HTML elements, such as `div` or `span`
+    Html,
+
+    /// SVG elements, such as `svg` or `circle`
+    Svg,
+
+    /// MathML elements, such as `math` or `mfrac`
+    MathMl,
+}
+
+impl Namespace {
+    /// Returns the namespace URI, such as `http://www.w3.org/2000/svg`.
+    pub fn uri(&self) -> &'static str {
+        match *self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+        }
+    }
 }
 
 /// A `DomNode` with a key
@@ -201,9 +346,12 @@ impl<M, T: DomNode<M>> DomNode<M> for WithKey<M, T> {
     type WithoutListeners = WithKey<M, T::WithoutListeners>;
 
     fn key(&self) -> Option<u32> { Some(self.1) }
-    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
         self.0.get_attribute(index)
     }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.0.get_property(index)
+    }
     fn children(&self) -> &Self::Children {
         self.0.children()
     }
@@ -236,11 +384,15 @@ impl<M, T, A> DomNode<M> for WithAttributes<M, T, A> where T: DomNode<M>, A: AsR
     type Listeners = T::Listeners;
     type WithoutListeners = WithAttributes<M, T::WithoutListeners, A>;
     fn key(&self) -> Option<u32> { self.node.key() }
-    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
         let attributes = self.attributes.as_ref();
-        attributes
-            .get(index)
-            .or_else(|| self.node.get_attribute(index - attributes.len()))
+        match attributes.get(index) {
+            Some(&(name, ref value)) => Some((name, value)),
+            None => self.node.get_attribute(index - attributes.len()),
+        }
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.node.get_property(index)
     }
     fn children(&self) -> &Self::Children {
         self.node.children()
@@ -265,6 +417,54 @@ impl<M, T, A> DomNode<M> for WithAttributes<M, T, A> where T: DomNode<M>, A: AsR
     fn value(&self) -> DomValue { self.node.value() }
 }
 
+/// Wrapper for `DomNode`s that adds DOM properties.
+pub struct WithProperties<M, T: DomNode<M>, P: AsRef<[KeyValue]>> {
+    node: T,
+    properties: P,
+    _marker: PhantomData<M>
+}
+impl<M, T: DomNode<M>, P: AsRef<[KeyValue]>> DomNodes<M> for WithProperties<M, T, P> {
+    fn process_all<'a, Q: DomNodeProcessor<'a, M>>(&'a self, acc: &mut Q::Acc) -> Result<(), Q::Error> {
+        Q::get_processor()(acc, self)
+    }
+}
+impl<M, T, P> DomNode<M> for WithProperties<M, T, P> where T: DomNode<M>, P: AsRef<[KeyValue]> {
+    type Children = T::Children;
+    type Listeners = T::Listeners;
+    type WithoutListeners = WithProperties<M, T::WithoutListeners, P>;
+    fn key(&self) -> Option<u32> { self.node.key() }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.node.get_attribute(index)
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        let properties = self.properties.as_ref();
+        properties
+            .get(index)
+            .or_else(|| self.node.get_property(index - properties.len()))
+    }
+    fn children(&self) -> &Self::Children {
+        self.node.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.node.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        self.node.children_and_listeners()
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.node.split_listeners();
+        (
+            WithProperties {
+                node,
+                properties: self.properties,
+                _marker: PhantomData,
+            },
+            listeners
+        )
+    }
+    fn value(&self) -> DomValue<'_> { self.node.value() }
+}
+
 /// Wrapper for `DomNode`s that adds listeners.
 pub struct WithListeners<M, T: DomNode<M, Listeners=EmptyListeners>, L: Listeners<M>> {
     node: T,
@@ -283,9 +483,12 @@ impl<M, T, L> DomNode<M> for WithListeners<M, T, L>
     type Listeners = L;
     type WithoutListeners = T;
     fn key(&self) -> Option<u32> { self.node.key() }
-    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
         self.node.get_attribute(index)
     }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.node.get_property(index)
+    }
     fn children(&self) -> &Self::Children {
         self.node.children()
     }
@@ -301,6 +504,89 @@ impl<M, T, L> DomNode<M> for WithListeners<M, T, L>
     fn value(&self) -> DomValue { self.node.value() }
 }
 
+/// Wrapper for `DomNode`s that converts the messages published in its subtree using function `F`.
+#[repr(transparent)]
+pub struct MapMessages<M, N, T, F>(MapperMarker<M, N, F>, T);
+impl<M, N, T, F> MapMessages<M, N, T, F> {
+    pub(crate) fn from_ref(node: &T) -> &Self {
+        // Safe because `MapMessages` is a transparent wrapper around `T`
+        unsafe { &*(node as *const T as *const Self) }
+    }
+}
+impl<M, N, T: DomNode<M>, F: Fn(M) -> N + Copy> DomNodes<N> for MapMessages<M, N, T, F> {
+    fn process_all<'a, P: DomNodeProcessor<'a, N>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, N, T, F> DomNode<N> for MapMessages<M, N, T, F>
+    where T: DomNode<M>, F: Fn(M) -> N + Copy
+{
+    type Children = MapNodes<M, N, T::Children, F>;
+    type Listeners = MapListeners<M, N, T::Listeners, F>;
+    type WithoutListeners = MapMessagesWithoutListeners<M, N, T::WithoutListeners, F>;
+    fn key(&self) -> Option<u32> { self.1.key() }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.1.get_attribute(index)
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.1.get_property(index)
+    }
+    fn children(&self) -> &Self::Children {
+        MapNodes::from_ref(self.1.children())
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        MapListeners::from_ref(self.1.listeners())
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        let (children, listeners) = self.1.children_and_listeners();
+        (MapNodes::from_ref(children), MapListeners::from_ref(listeners))
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.1.split_listeners();
+        (MapMessagesWithoutListeners(PhantomData, node), MapListeners::new(listeners))
+    }
+    fn value(&self) -> DomValue<'_> { self.1.value() }
+}
+
+/// `MapMessages` wrapper for a `DomNode` whose listeners have been split off.
+///
+/// This only needs to convert the messages of the node's children.
+pub struct MapMessagesWithoutListeners<M, N, T, F>(MapperMarker<M, N, F>, T);
+impl<M, N, T, F> DomNodes<N> for MapMessagesWithoutListeners<M, N, T, F>
+    where T: DomNode<M, Listeners=EmptyListeners>, F: Fn(M) -> N + Copy
+{
+    fn process_all<'a, P: DomNodeProcessor<'a, N>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, N, T, F> DomNode<N> for MapMessagesWithoutListeners<M, N, T, F>
+    where T: DomNode<M, Listeners=EmptyListeners>, F: Fn(M) -> N + Copy
+{
+    type Children = MapNodes<M, N, T::Children, F>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { self.1.key() }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.1.get_attribute(index)
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        self.1.get_property(index)
+    }
+    fn children(&self) -> &Self::Children {
+        MapNodes::from_ref(self.1.children())
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (MapNodes::from_ref(self.1.children()), EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.1.value() }
+}
+
 /// Iterator over the attributes of a `DomNode`
 pub struct AttributeIter<'a, M, T: DomNode<M> + 'a> {
     node: &'a T,
@@ -309,7 +595,7 @@ pub struct AttributeIter<'a, M, T: DomNode<M> + 'a> {
 }
 
 impl<'a, M, T: DomNode<M>> Iterator for AttributeIter<'a, M, T> {
-    type Item = &'a KeyValue;
+    type Item = (&'a str, &'a AttributeValue);
     fn next(&mut self) -> Option<Self::Item> {
         let res = self.node.get_attribute(self.index);
         self.index += 1;
@@ -317,6 +603,22 @@ impl<'a, M, T: DomNode<M>> Iterator for AttributeIter<'a, M, T> {
     }
 }
 
+/// Iterator over the DOM properties of a `DomNode`
+pub struct PropertyIter<'a, M, T: DomNode<M> + 'a> {
+    node: &'a T,
+    index: usize,
+    _marker: PhantomData<M>,
+}
+
+impl<'a, M, T: DomNode<M>> Iterator for PropertyIter<'a, M, T> {
+    type Item = &'a KeyValue;
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.node.get_property(self.index);
+        self.index += 1;
+        res
+    }
+}
+
 static EMPTY_NODES_REF: &'static () = &();
 static EMPTY_LISTN_REF: &'static EmptyListeners = &EmptyListeners;
 
@@ -332,7 +634,7 @@ impl<M> DomNode<M> for String {
     type Listeners = EmptyListeners;
     type WithoutListeners = String;
     fn key(&self) -> Option<u32> { None }
-    fn get_attribute(&self, _index: usize) -> Option<&KeyValue> {
+    fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> {
         None
     }
     fn children(&self) -> &Self::Children {
@@ -360,7 +662,7 @@ impl<'a, M> DomNode<M> for &'a str {
     type Listeners = EmptyListeners;
     type WithoutListeners = Self;
     fn key(&self) -> Option<u32> { None }
-    fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+    fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> { None }
     fn children(&self) -> &Self::Children {
         EMPTY_NODES_REF
     }
@@ -375,3 +677,130 @@ impl<'a, M> DomNode<M> for &'a str {
     }
     fn value(&self) -> DomValue { DomValue::Text(self) }
 }
+
+/// Creates a node containing pre-rendered HTML markup, such as CMS content or
+/// syntax-highlighted code.
+///
+/// **The markup is not escaped or sanitized in any way.** `HtmlWriter` writes it out verbatim
+/// and `web_render` inserts it using `innerHTML`, so passing untrusted input to `raw_html`
+/// allows for cross-site scripting attacks. Text that is not known to be safe should be added to
+/// the tree as a `&str` or `String` instead, which is always escaped.
+///
+/// When rendered in the browser, the markup is placed inside of a wrapper `span` which is only
+/// replaced when the markup changes. `HtmlWriter` doesn't write the wrapper, so hydrating
+/// server-rendered markup moves the matching nodes into a new `span`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::raw_html;
+/// use domafic::tags::div;
+/// use std::marker::PhantomData;
+///
+/// let highlighted = "<span class=\"kw\">fn</span> main() {}";
+/// let code = div((
+///     raw_html(highlighted),
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+///
+/// assert_eq!(
+///     "<div><span class=\"kw\">fn</span> main() {}</div>",
+///     code.to_string()
+/// );
+/// ```
+pub fn raw_html<S: AsRef<str>>(markup: S) -> RawHtml<S> {
+    RawHtml(markup)
+}
+
+/// Trusted HTML markup that is inserted into the document without escaping.
+///
+/// Created by `raw_html`.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct RawHtml<S: AsRef<str>>(S);
+
+impl<M, S: AsRef<str>> DomNodes<M> for RawHtml<S> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, S: AsRef<str>> DomNode<M> for RawHtml<S> {
+    type Children = ();
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> { None }
+    fn children(&self) -> &Self::Children {
+        EMPTY_NODES_REF
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { DomValue::RawHtml(self.0.as_ref()) }
+}
+
+/// Creates a marker node at which streamed HTML is flushed to the underlying writer.
+///
+/// Flush points don't produce any markup or DOM nodes, but when
+/// writing with `HtmlFormat::stream_html` or `Document::stream_html`, everything written before
+/// the flush point is sent on before the rest of the tree is written. Place them before
+/// expensive parts of the page so that the shell of the page reaches the client early.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::flush_point;
+/// use domafic::tags::{div, h1, p};
+/// use std::marker::PhantomData;
+///
+/// let page = div((
+///     h1("Results"),
+///     flush_point(),
+///     p("Lots of results..."),
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+///
+/// assert_eq!("<div><h1>Results</h1><p>Lots of results...</p></div>", page.to_string());
+/// ```
+pub fn flush_point() -> FlushPoint {
+    FlushPoint
+}
+
+/// A marker node at which streamed HTML is flushed.
+///
+/// Created by `flush_point`.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct FlushPoint;
+
+impl<M> DomNodes<M> for FlushPoint {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M> DomNode<M> for FlushPoint {
+    type Children = ();
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> { None }
+    fn children(&self) -> &Self::Children {
+        EMPTY_NODES_REF
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { DomValue::FlushPoint }
+}
@@ -1,13 +1,14 @@
 extern crate marksman_escape;
 use self::marksman_escape::Escape;
 
-use {DomNode, DomNodes, DomValue};
+use {AttributeValue, DomNode, DomNodes, DomValue, Namespace};
 use processors::DomNodeProcessor;
+use tags::{is_inline_element, is_valid_attribute_name, is_void_element};
 
 // This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::convert::Infallible;
 use std::marker::PhantomData;
-use std::fmt;
-use std::io;
+use std::{fmt, io, str};
 
 /// Type to use for processing a `DomNode` tree and writing it to HTML.
 ///
@@ -22,40 +23,738 @@ impl<'a, M, W: io::Write> DomNodeProcessor<'a, M> for HtmlWriter<W> {
     fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
         fn add_node<M, W, T>(w: &mut W, node: &T) -> Result<(), io::Error>
                 where W: io::Write, T: DomNode<M> {
-            match node.value() {
-                DomValue::Element { tag: tagname } => {
-                    write!(w, "<{}", tagname)?;
-                    for attr in node.attributes() {
-                        write!(w, " {}=\"{}\"", attr.0, attr.1)?;
-                    }
-                    write!(w, ">")?;
-                    node.children().process_all::<HtmlWriter<W>>(w)?;
-                    write!(w, "</{}>", tagname)
+            write_node(&mut Output::new(IoSink::from_mut(w), HtmlFormat::new()), node)
+        }
+        add_node
+    }
+}
+
+/// Type to use for processing a `DomNode` tree and writing it to HTML through `fmt::Write`,
+/// such as a `String` or a `fmt::Formatter`.
+///
+/// Unlike `HtmlWriter`, no intermediate byte buffer is needed to produce a `String`. Errors,
+/// such as invalid attribute names, are reported as `fmt::Error`.
+///
+/// This type should not ever need to be instantiated. Instead, simply
+/// name the type in calls to `DomNodes::process_all::<HtmlFmtWriter<...>>(...)`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct HtmlFmtWriter<W: fmt::Write>(PhantomData<W>);
+impl<'a, M, W: fmt::Write> DomNodeProcessor<'a, M> for HtmlFmtWriter<W> {
+    type Acc = W;
+    type Error = fmt::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, W, T>(w: &mut W, node: &T) -> Result<(), fmt::Error>
+                where W: fmt::Write, T: DomNode<M> {
+            write_node(&mut Output::new(FmtSink::from_mut(w), HtmlFormat::new()), node)
+        }
+        add_node
+    }
+}
+
+/// Destination for HTML output, allowing the same writer to target both `io::Write` and
+/// `fmt::Write`.
+trait Sink {
+    type Error;
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error>;
+    fn invalid_input(message: fmt::Arguments) -> Self::Error;
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+#[repr(transparent)]
+struct IoSink<W>(W);
+impl<W: io::Write> IoSink<W> {
+    fn from_mut(w: &mut W) -> &mut IoSink<W> {
+        // Safe because `IoSink` is a transparent wrapper around `W`
+        unsafe { &mut *(w as *mut W as *mut IoSink<W>) }
+    }
+}
+impl<W: io::Write> Sink for IoSink<W> {
+    type Error = io::Error;
+    fn write_str(&mut self, s: &str) -> Result<(), io::Error> {
+        self.0.write_all(s.as_bytes())
+    }
+    fn invalid_input(message: fmt::Arguments) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+    }
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.0.flush()
+    }
+}
+
+#[repr(transparent)]
+struct FmtSink<W>(W);
+impl<W: fmt::Write> FmtSink<W> {
+    fn from_mut(w: &mut W) -> &mut FmtSink<W> {
+        // Safe because `FmtSink` is a transparent wrapper around `W`
+        unsafe { &mut *(w as *mut W as *mut FmtSink<W>) }
+    }
+}
+impl<W: fmt::Write> Sink for FmtSink<W> {
+    type Error = fmt::Error;
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.0.write_str(s)
+    }
+    fn invalid_input(_message: fmt::Arguments) -> fmt::Error {
+        fmt::Error
+    }
+    fn flush(&mut self) -> Result<(), fmt::Error> {
+        Ok(())
+    }
+}
+
+/// Options controlling the style of the HTML written for a `DomNode`.
+///
+/// By default (`HtmlFormat::new()`), HTML is written on a single line with conservative
+/// escaping, exactly as `DomNode::write_html` writes it.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::html_writer::HtmlFormat;
+/// use domafic::tags::{div, h1, p, b};
+/// use std::marker::PhantomData;
+///
+/// let page = div((
+///     h1("Title"),
+///     p(("Some ", b("bold"), " text!")),
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+///
+/// let mut pretty = String::new();
+/// HtmlFormat::pretty(2).write_html_fmt(&page, &mut pretty).unwrap();
+/// assert_eq!("<div>\n  <h1>Title</h1>\n  <p>Some <b>bold</b> text&#33;</p>\n</div>", pretty);
+///
+/// let mut minified = String::new();
+/// HtmlFormat::minified().write_html_fmt(&page, &mut minified).unwrap();
+/// assert_eq!("<div><h1>Title</h1><p>Some <b>bold</b> text!</p></div>", minified);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct HtmlFormat {
+    indent: Option<usize>,
+    escaping: Escaping,
+}
+
+/// How text and attribute values are escaped by `HtmlFormat`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Escaping {
+    /// Escapes every character that could be significant in any HTML context, such as `!`
+    /// and `=`, in addition to `&`, `<`, `>` and quotes.
+    Conservative,
+
+    /// Uses the shortest legal escaping: `&` and `<` in text, and `&` and `"` in attribute
+    /// values.
+    Minimal,
+}
+
+impl Default for HtmlFormat {
+    fn default() -> HtmlFormat {
+        HtmlFormat::new()
+    }
+}
+
+impl HtmlFormat {
+    /// Writes HTML on a single line with conservative escaping.
+    pub fn new() -> HtmlFormat {
+        HtmlFormat { indent: None, escaping: Escaping::Conservative }
+    }
+
+    /// Writes HTML with each block-level element on its own line, indented by `indent_width`
+    /// spaces per level.
+    ///
+    /// Inline elements (see `tags::is_inline_element`), `svg` and `math` elements, and text stay
+    /// on the same line, since whitespace between them is significant. The contents of `pre`,
+    /// `textarea`, `script` and `style` elements are always written exactly as they are.
+    pub fn pretty(indent_width: usize) -> HtmlFormat {
+        HtmlFormat::new().with_indent(indent_width)
+    }
+
+    /// Writes HTML on a single line with minimal escaping.
+    pub fn minified() -> HtmlFormat {
+        HtmlFormat::new().with_escaping(Escaping::Minimal)
+    }
+
+    /// Sets the number of spaces used to indent each level of block-level elements.
+    pub fn with_indent(self, indent_width: usize) -> HtmlFormat {
+        HtmlFormat { indent: Some(indent_width), ..self }
+    }
+
+    /// Sets how text and attribute values are escaped.
+    pub fn with_escaping(self, escaping: Escaping) -> HtmlFormat {
+        HtmlFormat { escaping, ..self }
+    }
+
+    /// Writes the HTML representation of `node` to `writer` in this format.
+    pub fn write_html<M, T, W>(&self, node: &T, writer: &mut W) -> io::Result<()>
+        where T: DomNode<M>, W: io::Write
+    {
+        write_node(&mut Output::new(IoSink::from_mut(writer), *self), node)
+    }
+
+    /// Writes the HTML representation of `node` to a `fmt::Write`, such as a `String`, in this
+    /// format.
+    pub fn write_html_fmt<M, T, W>(&self, node: &T, writer: &mut W) -> fmt::Result
+        where T: DomNode<M>, W: fmt::Write
+    {
+        write_node(&mut Output::new(FmtSink::from_mut(writer), *self), node)
+    }
+
+    /// Streams the HTML representation of `node` to `writer` in this format.
+    ///
+    /// Output is buffered, and the buffer is flushed to `writer` (and `writer` itself is flushed)
+    /// at the boundaries chosen by `flush` and once the whole tree has been written. This allows,
+    /// for example, an HTTP server to send each part of a large page as a chunk as soon as it is
+    /// ready.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use domafic::flush_point;
+    /// use domafic::html_writer::{FlushPolicy, HtmlFormat};
+    /// use domafic::tags::{div, h1, p};
+    /// use std::marker::PhantomData;
+    ///
+    /// let page = div((
+    ///     h1("Results"),
+    ///     flush_point(),
+    ///     p("Lots of results..."),
+    ///     // We need to manually mark the message type since it can't be inferred
+    ///     PhantomData::<()>,
+    /// ));
+    ///
+    /// let mut response = Vec::new();
+    /// HtmlFormat::new().stream_html(&page, &mut response, FlushPolicy::new()).unwrap();
+    /// assert_eq!(
+    ///     "<div><h1>Results</h1><p>Lots of results...</p></div>",
+    ///     String::from_utf8(response).unwrap()
+    /// );
+    /// ```
+    pub fn stream_html<M, T, W>(&self, node: &T, writer: &mut W, flush: FlushPolicy)
+        -> io::Result<()>
+        where T: DomNode<M>, W: io::Write
+    {
+        stream(writer, *self, flush, |w| write_node(w, node))
+    }
+}
+
+/// The boundaries at which streamed HTML is flushed by `HtmlFormat::stream_html` and
+/// `Document::stream_html`.
+///
+/// Streamed HTML is always flushed once everything has been written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FlushPolicy {
+    after_head: bool,
+    at_flush_points: bool,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> FlushPolicy {
+        FlushPolicy::new()
+    }
+}
+
+impl FlushPolicy {
+    /// Flushes after the closing tag of the `head` element and at every `flush_point`.
+    pub fn new() -> FlushPolicy {
+        FlushPolicy { after_head: true, at_flush_points: true }
+    }
+
+    /// Only flushes once everything has been written.
+    pub fn never() -> FlushPolicy {
+        FlushPolicy { after_head: false, at_flush_points: false }
+    }
+
+    /// Sets whether to flush after the closing tag of the `head` element.
+    pub fn with_flush_after_head(self, after_head: bool) -> FlushPolicy {
+        FlushPolicy { after_head, ..self }
+    }
+
+    /// Sets whether to flush at the nodes created by `flush_point`.
+    pub fn with_flush_points(self, at_flush_points: bool) -> FlushPolicy {
+        FlushPolicy { at_flush_points, ..self }
+    }
+}
+
+/// Buffers the output written by `write`, flushing it at the boundaries chosen by `flush`.
+fn stream<W, F>(writer: &mut W, format: HtmlFormat, flush: FlushPolicy, write: F)
+    -> io::Result<()>
+    where W: io::Write, F: FnOnce(&mut Output<IoSink<io::BufWriter<&mut W>>>) -> io::Result<()>
+{
+    let mut buffered = io::BufWriter::new(writer);
+    {
+        let mut output = Output::new(IoSink::from_mut(&mut buffered), format);
+        output.flush = flush;
+        write(&mut output)?;
+    }
+    io::Write::flush(&mut buffered)
+}
+
+struct Output<'w, S: Sink + 'w> {
+    sink: &'w mut S,
+    format: HtmlFormat,
+    depth: usize,
+    // Whether the nodes currently being written are placed on separate lines
+    block_layout: bool,
+    // Whether the last node written was inline, so that following inline nodes stay on its line
+    after_inline: bool,
+    // Whether the nodes currently being written are inside of a whitespace-preserving element
+    preformatted: bool,
+    // Whether head declarations are skipped because they have been moved to a `Document`'s head
+    hoist_head: bool,
+    // The boundaries at which the sink is flushed while streaming
+    flush: FlushPolicy,
+}
+
+impl<'w, S: Sink> Output<'w, S> {
+    fn new(sink: &'w mut S, format: HtmlFormat) -> Output<'w, S> {
+        Output {
+            sink,
+            format,
+            depth: 0,
+            block_layout: false,
+            after_inline: false,
+            preformatted: false,
+            hoist_head: false,
+            flush: FlushPolicy::never(),
+        }
+    }
+
+    fn write_str(&mut self, s: &str) -> Result<(), S::Error> {
+        self.sink.write_str(s)
+    }
+
+    fn newline(&mut self) -> Result<(), S::Error> {
+        const SPACES: &str = "                ";
+        self.sink.write_str("\n")?;
+        let mut remaining = self.depth * self.format.indent.unwrap_or(0);
+        while remaining > 0 {
+            let len = remaining.min(SPACES.len());
+            self.sink.write_str(&SPACES[..len])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+}
+
+struct SinkWriter<'w, S: 'w>(PhantomData<&'w mut S>);
+impl<'a, 'w, M, S: Sink> DomNodeProcessor<'a, M> for SinkWriter<'w, S> {
+    type Acc = Output<'w, S>;
+    type Error = S::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        write_node
+    }
+}
+
+fn write_node<M, S, T>(w: &mut Output<S>, node: &T) -> Result<(), S::Error>
+        where S: Sink, T: DomNode<M> {
+    let value = node.value();
+    if value == DomValue::FlushPoint {
+        return if w.flush.at_flush_points { w.sink.flush() } else { Ok(()) };
+    }
+    if w.hoist_head && is_head_declaration(value) {
+        return Ok(());
+    }
+    let is_inline = match value {
+        DomValue::Element { tag, namespace } => is_inline(tag, namespace),
+        DomValue::Text(_) | DomValue::RawHtml(_) | DomValue::FlushPoint => true,
+    };
+    if w.block_layout && !(is_inline && w.after_inline) {
+        w.newline()?;
+    }
+    w.after_inline = is_inline;
+
+    match value {
+        DomValue::Element { tag: tagname, namespace } => {
+            w.write_str("<")?;
+            w.write_str(tagname)?;
+            // Properties are written as attributes, which set the initial state of the element
+            let attributes = || node.attributes()
+                .chain(node.properties().map(|&(name, ref value)| (name, value)));
+            for (index, (name, value)) in attributes().enumerate() {
+                if !is_valid_attribute_name(name) {
+                    return Err(S::invalid_input(
+                        format_args!("Invalid attribute name: {:?}", name)
+                    ));
+                }
+                // When a name is repeated, the last attribute with that name wins
+                if attributes().skip(index + 1).any(|later| later.0 == name) {
+                    continue;
                 }
-                DomValue::Text(text) => {
-                    for escaped_u8 in Escape::new(text.bytes()) {
-                        w.write(&[escaped_u8])?;
+                match *value {
+                    // Boolean attributes are enabled by their presence alone
+                    AttributeValue::Bool(true) => {
+                        w.write_str(" ")?;
+                        w.write_str(name)?;
+                    }
+                    AttributeValue::Bool(false) => {},
+                    ref value => {
+                        w.write_str(" ")?;
+                        w.write_str(name)?;
+                        w.write_str("=\"")?;
+                        write_escaped_display(w.sink, value, w.format.escaping)?;
+                        w.write_str("\"")?;
                     }
-                    Ok(())
                 }
             }
+            // SVG and MathML elements may be self-closing
+            if namespace != Namespace::Html && !has_children(node) {
+                return w.write_str(" />");
+            }
+            // HTML void elements have no closing tag and cannot have children
+            if namespace == Namespace::Html && is_void_element(tagname) {
+                if has_children(node) {
+                    return Err(S::invalid_input(
+                        format_args!("Void element <{}> cannot have children", tagname)
+                    ));
+                }
+                return w.write_str(">");
+            }
+            w.write_str(">")?;
+
+            let (block_layout, preformatted) = (w.block_layout, w.preformatted);
+            // Inline SVG and MathML are kept on one line, as whitespace in their text is rendered
+            w.preformatted = preformatted || matches!(tagname, "svg" | "math") ||
+                (namespace == Namespace::Html && is_preformatted_element(tagname));
+            w.block_layout = w.format.indent.is_some() && !w.preformatted &&
+                has_block_children(node);
+            w.after_inline = false;
+            w.depth += 1;
+            node.children().process_all::<SinkWriter<S>>(w)?;
+            w.depth -= 1;
+            if w.block_layout {
+                w.newline()?;
+            }
+            w.block_layout = block_layout;
+            w.preformatted = preformatted;
+            w.after_inline = is_inline;
+
+            w.write_str("</")?;
+            w.write_str(tagname)?;
+            w.write_str(">")?;
+            if w.flush.after_head && is_head_element(value) {
+                w.sink.flush()?;
+            }
+            Ok(())
         }
-        add_node
+        DomValue::Text(text) => write_escaped(w.sink, text, w.format.escaping, false),
+        DomValue::RawHtml(markup) => w.write_str(markup),
+        // Handled above
+        DomValue::FlushPoint => Ok(()),
     }
 }
 
+fn is_head_element(value: DomValue) -> bool {
+    value == DomValue::Element { tag: "head", namespace: Namespace::Html }
+}
+
+// Whether an element is moved into the head of a `Document` from anywhere in the body's tree
+fn is_head_declaration(value: DomValue) -> bool {
+    match value {
+        DomValue::Element { tag, namespace: Namespace::Html } =>
+            matches!(tag, "head" | "title" | "meta" | "link" | "script"),
+        _ => false,
+    }
+}
+
+/// The parts of a `Document` that aren't found in its body's tree.
+pub(crate) struct DocumentParts<'d> {
+    pub(crate) lang: Option<&'d str>,
+    // Title to use if no `title` elements are declared in the tree
+    pub(crate) title: Option<&'d str>,
+    // `id` and contents of a `script` embedding JSON, already escaped for use in a `script`
+    pub(crate) json_script: Option<(&'d str, &'d str)>,
+}
+
+impl<'d> DocumentParts<'d> {
+    pub(crate) fn write_html<M, T, W>(&self, body: &T, format: HtmlFormat, writer: &mut W)
+        -> io::Result<()>
+        where T: DomNode<M>, W: io::Write
+    {
+        self.write(body, &mut Output::new(IoSink::from_mut(writer), format))
+    }
+
+    pub(crate) fn write_html_fmt<M, T, W>(&self, body: &T, format: HtmlFormat, writer: &mut W)
+        -> fmt::Result
+        where T: DomNode<M>, W: fmt::Write
+    {
+        self.write(body, &mut Output::new(FmtSink::from_mut(writer), format))
+    }
+
+    pub(crate) fn stream_html<M, T, W>(
+        &self, body: &T, format: HtmlFormat, writer: &mut W, flush: FlushPolicy
+    ) -> io::Result<()>
+        where T: DomNode<M>, W: io::Write
+    {
+        stream(writer, format, flush, |w| self.write(body, w))
+    }
+
+    fn write<M, T, S>(&self, body: &T, w: &mut Output<S>) -> Result<(), S::Error>
+        where T: DomNode<M>, S: Sink
+    {
+        // The head declarations are collected in a single pass before anything is written
+        let mut head = HeadAcc { nodes: Vec::new(), title: None };
+        match body.process_all::<HeadCollector<S>>(&mut head) {
+            Ok(()) => {},
+            Err(never) => match never {},
+        }
+
+        w.block_layout = w.format.indent.is_some();
+        w.write_str("<!DOCTYPE html>")?;
+        start_line(w, 0)?;
+        w.write_str("<html")?;
+        if let Some(lang) = self.lang {
+            w.write_str(" lang=\"")?;
+            write_escaped(w.sink, lang, w.format.escaping, true)?;
+            w.write_str("\"")?;
+        }
+        w.write_str(">")?;
+
+        start_line(w, 1)?;
+        w.write_str("<head>")?;
+        if let (None, Some(title)) = (head.title, self.title) {
+            start_line(w, 2)?;
+            w.write_str("<title>")?;
+            write_escaped(w.sink, title, w.format.escaping, false)?;
+            w.write_str("</title>")?;
+        }
+        w.depth = 2;
+        for node in head.nodes {
+            node.write_head(w)?;
+        }
+        if let Some((id, json)) = self.json_script {
+            start_line(w, 2)?;
+            w.write_str("<script type=\"application/json\" id=\"")?;
+            w.write_str(id)?;
+            w.write_str("\">")?;
+            w.write_str(json)?;
+            w.write_str("</script>")?;
+        }
+        start_line(w, 1)?;
+        w.write_str("</head>")?;
+        if w.flush.after_head {
+            w.sink.flush()?;
+        }
+
+        w.hoist_head = true;
+        w.after_inline = false;
+        if body.value() == (DomValue::Element { tag: "body", namespace: Namespace::Html }) {
+            w.depth = 1;
+            write_node(w, body)?;
+        } else {
+            start_line(w, 1)?;
+            w.write_str("<body>")?;
+            w.depth = 2;
+            write_node(w, body)?;
+            start_line(w, 1)?;
+            w.write_str("</body>")?;
+        }
+
+        start_line(w, 0)?;
+        w.write_str("</html>")
+    }
+}
+
+fn start_line<S: Sink>(w: &mut Output<S>, depth: usize) -> Result<(), S::Error> {
+    w.depth = depth;
+    if w.block_layout {
+        w.newline()?;
+    }
+    Ok(())
+}
+
+// A node moved into the head of a document, which is written once the whole tree has been searched
+trait HeadNode<M, S: Sink> {
+    fn write_head(&self, w: &mut Output<S>) -> Result<(), S::Error>;
+}
+impl<M, S: Sink, T: DomNode<M>> HeadNode<M, S> for T {
+    fn write_head(&self, w: &mut Output<S>) -> Result<(), S::Error> {
+        write_node(w, self)
+    }
+}
+
+struct HeadAcc<'a, M: 'a, S: Sink + 'a> {
+    nodes: Vec<&'a dyn HeadNode<M, S>>,
+    // Index of the last `title` in `nodes`. Only the last title declared is written.
+    title: Option<usize>,
+}
+
+impl<'a, M, S: Sink> HeadAcc<'a, M, S> {
+    fn push<T: DomNode<M>>(&mut self, node: &'a T) {
+        if node.value() == (DomValue::Element { tag: "title", namespace: Namespace::Html }) {
+            if let Some(index) = self.title {
+                self.nodes.remove(index);
+            }
+            self.title = Some(self.nodes.len());
+        }
+        self.nodes.push(node);
+    }
+}
+
+// Collects the head declarations in a tree, along with the children of any `head` elements
+struct HeadCollector<S>(PhantomData<S>);
+impl<'a, M: 'a, S: Sink + 'a> DomNodeProcessor<'a, M> for HeadCollector<S> {
+    type Acc = HeadAcc<'a, M, S>;
+    type Error = Infallible;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn collect<'a, M, S, T>(acc: &mut HeadAcc<'a, M, S>, node: &'a T) -> Result<(), Infallible>
+            where S: Sink, T: DomNode<M>
+        {
+            let value = node.value();
+            if is_head_element(value) {
+                node.children().process_all::<HeadChildCollector<S>>(acc)
+            } else if is_head_declaration(value) {
+                acc.push(node);
+                Ok(())
+            } else {
+                node.children().process_all::<HeadCollector<S>>(acc)
+            }
+        }
+        collect
+    }
+}
+
+// Collects the children of a `head` element
+struct HeadChildCollector<S>(PhantomData<S>);
+impl<'a, M: 'a, S: Sink + 'a> DomNodeProcessor<'a, M> for HeadChildCollector<S> {
+    type Acc = HeadAcc<'a, M, S>;
+    type Error = Infallible;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn collect<'a, M, S, T>(acc: &mut HeadAcc<'a, M, S>, node: &'a T) -> Result<(), Infallible>
+            where S: Sink, T: DomNode<M>
+        {
+            acc.push(node);
+            Ok(())
+        }
+        collect
+    }
+}
+
+// Whether an element is laid out inline with text. The root `svg` and `math` elements are inline,
+// although the elements inside of them are not.
+fn is_inline(tag: &str, namespace: Namespace) -> bool {
+    match tag {
+        "svg" | "math" => true,
+        tag => namespace == Namespace::Html && is_inline_element(tag),
+    }
+}
+
+fn is_preformatted_element(tag: &str) -> bool {
+    matches!(tag, "pre" | "textarea" | "script" | "style")
+}
+
+fn write_escaped<S: Sink>(w: &mut S, text: &str, escaping: Escaping, in_attribute: bool)
+    -> Result<(), S::Error>
+{
+    // Unescaped runs of text are written all at once
+    let mut unescaped_start = 0;
+    for (index, byte) in text.bytes().enumerate() {
+        if byte.is_ascii_alphanumeric() || !byte.is_ascii() {
+            continue;
+        }
+
+        let mut entity = [0; 8];
+        let mut entity_len = 0;
+        match escaping {
+            Escaping::Conservative => {
+                for escaped_byte in Escape::new(Some(byte).into_iter()) {
+                    entity[entity_len] = escaped_byte;
+                    entity_len += 1;
+                }
+            }
+            Escaping::Minimal => {
+                let escaped: &[u8] = match byte {
+                    b'&' => b"&amp;",
+                    b'<' if !in_attribute => b"&lt;",
+                    b'"' if in_attribute => b"&quot;",
+                    _ => b"",
+                };
+                entity[..escaped.len()].copy_from_slice(escaped);
+                entity_len = escaped.len();
+            }
+        }
+        if entity_len > 1 {
+            w.write_str(&text[unescaped_start..index])?;
+            // Escaped entities are always ASCII
+            w.write_str(str::from_utf8(&entity[..entity_len]).unwrap())?;
+            unescaped_start = index + 1;
+        }
+    }
+    w.write_str(&text[unescaped_start..])
+}
+
+fn write_escaped_display<S, D>(w: &mut S, value: &D, escaping: Escaping) -> Result<(), S::Error>
+    where S: Sink, D: fmt::Display
+{
+    // Escapes the formatted value as it is written, rather than formatting it to a `String`
+    struct Escaper<'s, S: Sink + 's>(&'s mut S, Escaping, Option<S::Error>);
+    impl<'s, S: Sink> fmt::Write for Escaper<'s, S> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            write_escaped(self.0, s, self.1, true).map_err(|error| {
+                self.2 = Some(error);
+                fmt::Error
+            })
+        }
+    }
+
+    let mut escaper = Escaper(w, escaping, None);
+    match fmt::write(&mut escaper, format_args!("{}", value)) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => Err(escaper.2.unwrap_or_else(||
+            S::invalid_input(format_args!("Failed to format attribute value"))
+        )),
+    }
+}
+
+fn has_block_children<M, T: DomNode<M>>(node: &T) -> bool {
+    // Stops at the first block-level child by returning an error
+    struct FindBlockChild;
+    impl<'a, M> DomNodeProcessor<'a, M> for FindBlockChild {
+        type Acc = ();
+        type Error = ();
+
+        fn get_processor<T: DomNode<M>>() -> fn(&mut (), &T) -> Result<(), ()> {
+            fn check<M, T: DomNode<M>>(_acc: &mut (), node: &T) -> Result<(), ()> {
+                match node.value() {
+                    DomValue::Element { tag, namespace } if !is_inline(tag, namespace) => Err(()),
+                    _ => Ok(()),
+                }
+            }
+            check
+        }
+    }
+
+    node.children().process_all::<FindBlockChild>(&mut ()).is_err()
+}
+
+fn has_children<M, T: DomNode<M>>(node: &T) -> bool {
+    // Stops at the first child by returning an error. Flush points don't count, as they produce
+    // no markup.
+    struct FindChild;
+    impl<'a, M> DomNodeProcessor<'a, M> for FindChild {
+        type Acc = ();
+        type Error = ();
+
+        fn get_processor<T: DomNode<M>>() -> fn(&mut (), &T) -> Result<(), ()> {
+            fn found<M, T: DomNode<M>>(_acc: &mut (), node: &T) -> Result<(), ()> {
+                if node.value() == DomValue::FlushPoint { Ok(()) } else { Err(()) }
+            }
+            found
+        }
+    }
+
+    node.children().process_all::<FindChild>(&mut ()).is_err()
+}
+
 /// Wrapper struct to allow `DomNode`s to implement `Display` as html
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct HtmlDisplayable<'a, M, T: DomNode<M> + 'a>(pub &'a T, pub PhantomData<M>);
 
 impl<'a, M, T: DomNode<M>> fmt::Display for HtmlDisplayable<'a, M, T> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        // TODO the extra string allocation here is almost certainly avoidable
-        let mut string_buffer = Vec::new();
-        self.0.write_html(&mut string_buffer)
-            .map_err(|_| fmt::Error)?;
-        let string = String::from_utf8(string_buffer)
-            .map_err(|_| fmt::Error)?;
-        formatter.write_str(&string)
+        self.0.write_html_fmt(formatter)
     }
 }
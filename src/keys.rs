@@ -7,14 +7,14 @@ pub struct Keys {
 }
 impl Keys {
     /// Create a new `Keys` with no elements
-    #[cfg_attr(not(target_os = "emscripten"), allow(dead_code))]
+    #[cfg_attr(not(any(target_os = "emscripten", target_arch = "wasm32")), allow(dead_code))]
     pub fn new() -> Keys {
         Keys { size: 0, stack: [0; KEY_STACK_LEN as usize] }
     }
 
     /// Push a new key onto the `Keys`
     /// Immutable. Creates a new `Keys` with the top element.
-    #[cfg_attr(not(target_os = "emscripten"), allow(dead_code))]
+    #[cfg_attr(not(any(target_os = "emscripten", target_arch = "wasm32")), allow(dead_code))]
     pub fn push(&self, key: u32) -> Keys {
         let mut stack = self.stack; // Copied
 
@@ -30,6 +30,7 @@ impl Keys {
 }
 
 /// An iterator over keys into a `DomNode` tree.
+#[derive(Clone, Debug)]
 pub struct KeyIter(Keys, u32);
 
 impl Iterator for KeyIter {
@@ -53,6 +54,14 @@ impl Iterator for KeyIter {
 
 impl ExactSizeIterator for KeyIter {}
 
+impl KeyIter {
+    /// Collects the remaining keys into a `Keys`
+    #[cfg_attr(not(any(target_os = "emscripten", target_arch = "wasm32")), allow(dead_code))]
+    pub(crate) fn into_keys(self) -> Keys {
+        self.fold(Keys::new(), |keys, key| keys.push(key as u32))
+    }
+}
+
 impl IntoIterator for Keys {
     type Item = usize;
     type IntoIter = KeyIter;
@@ -52,9 +52,9 @@
 //! use domafic::listener::on;
 //!
 //! // If rendering client-side with asm.js or WebAssembly:
-//! #[cfg(target_os = "emscripten")]
+//! #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 //! use domafic::web_render::{run, JsIo};
-//! #[cfg(target_os = "emscripten")]
+//! #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 //! use domafic::KeyIter;
 //!
 //! type State = isize;
@@ -64,7 +64,7 @@
 //!     Decrement,
 //! }
 //!
-//! #[cfg(target_os = "emscripten")]
+//! #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 //! let update = |state: &mut State, msg: Msg, _: KeyIter, _: &JsIo<Msg>| {
 //!     *state = match msg {
 //!         Msg::Increment => *state + 1,
@@ -88,21 +88,26 @@
 //! };
 //!
 //! // If rendering server-side:
-//! #[cfg(not(target_os = "emscripten"))]
+//! #[cfg(not(any(target_os = "emscripten", target_arch = "wasm32")))]
 //! println!("HTML: {}", render(&0));
 //!
 //! // If rendering client-side with asm.js or WebAssembly:
-//! #[cfg(target_os = "emscripten")]
+//! #[cfg(any(target_os = "emscripten", target_arch = "wasm32"))]
 //! run("body", update, render, 0);
 //! ```
 //!
+//! To take over HTML that was already rendered on the server rather than rendering the page
+//! again from scratch, use `web_render::hydrate` in place of `run`.
+//!
 //! Check out more examples like this one
 //! [in the Github repository.](https://github.com/cramertj/domafic-rs/tree/master/examples)
 //!
 //! The above example, if compiled for an emscripten target
 //! (via `cargo build --target=asmjs-unknown-emscripten` or similar) will produce a Javascript file
 //! that, when included on a webpage, will replace the contents of "body" with the message
-//! "Hello from rust!", +/- buttons, and a number.
+//! "Hello from rust!", +/- buttons, and a number. It can also be compiled for plain WebAssembly
+//! (via `cargo build --target=wasm32-unknown-unknown`) and loaded with the `js/domafic.js` glue
+//! file from the repository, without needing emscripten.
 //!
 //! So how does this all work? When the call to `run` occurs, Domafic gives the initial state (0)
 //! to the renderer (our "render" function) which returns the initial page to display to the user.
@@ -128,9 +133,21 @@
 #![allow(unused_unsafe)]
 #![deny(missing_docs)]
 
+/// Type-erased `DomNode`s for recursive and heterogeneous views
+#[cfg(any(feature = "use_std", test))]
+pub mod boxed;
+
+/// Backend-agnostic diffing of rendered `DomNode` trees against a DOM
+#[cfg(any(feature = "use_std", test))]
+pub mod diff;
+
+/// Complete HTML documents with automatically managed heads
+#[cfg(any(feature = "use_std", test))]
+pub mod document;
+
 /// Trait for elements that can be drawn as to HTML DOM nodes
 pub mod dom_node;
-pub use dom_node::{DomNode, DomValue};
+pub use dom_node::{DomNode, DomValue, FlushPoint, Namespace, RawHtml, flush_point, raw_html};
 
 /// Types, traits and functions for writing a `DomNode` to HTML
 #[cfg(any(feature = "use_std", test))]
@@ -138,17 +155,34 @@ pub mod html_writer;
 
 mod keys;
 pub use keys::KeyIter;
+/// Parsing HTML into owned `DomNode` trees
+#[cfg(any(feature = "use_parser", test))]
+pub mod parser;
 /// Types, traits, and functions for creating event handlers
 pub mod listener;
 pub use listener::{Listener, Event, on};
+/// In-memory DOM for testing renderers without a browser
+#[cfg(any(feature = "use_std", test))]
+pub mod mock_dom;
+/// Serializable patches describing the changes between two renders
+#[cfg(any(feature = "use_std", test))]
+pub mod patch;
 /// Traits for processing collections of `DomNode`s or `Listener`s
 pub mod processors;
 pub use processors::{DomNodes, Listeners};
 /// Types and functions for creating tag elements such as `div`s or `span`s
 pub mod tags;
+/// Owned, comparable snapshots of rendered `DomNode` trees
+#[cfg(any(feature = "use_std", test))]
+pub mod vnode;
+
+/// Serialized application state for sharing between server and client
+#[cfg(feature = "use_serde")]
+pub mod state;
 
-/// Functions for interacting with a webpage when rendering client-side using asmjs or emscripten
-#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+/// Functions for interacting with a webpage when rendering client-side using asmjs, emscripten or
+/// plain `wasm32`
+#[cfg(all(feature = "web_render", any(target_os = "emscripten", target_arch = "wasm32")))]
 pub mod web_render;
 
 /// A mapping between an attribute key and value.
@@ -156,7 +190,7 @@ pub mod web_render;
 pub type KeyValue = (&'static str, AttributeValue);
 
 /// A value of a `DomNode` attribute.
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum AttributeValue {
 
     /// A value represented by a static string reference
@@ -166,30 +200,115 @@ pub enum AttributeValue {
     #[cfg(any(feature = "use_std", test))]
     OwnedStr(String),
 
-    /// A boolean value
+    /// A boolean attribute, such as `disabled` or `autofocus`.
+    ///
+    /// Like in HTML, the attribute is enabled by its presence alone: `Bool(true)` adds the
+    /// attribute without a value, while `Bool(false)` leaves it out entirely.
     Bool(bool),
 
-    // TODO: add numeric variants?
+    /// An integer value, such as a `width` or `tabindex`
+    Int(i64),
+
+    /// A floating-point value, such as a `step`
+    Float(f64),
+
+    /// A list of tokens separated by spaces, such as a `class` or `rel`
+    Tokens(&'static [&'static str]),
+
+    /// A list of tokens separated by spaces, represented by owned `String`s
+    #[cfg(any(feature = "use_std", test))]
+    OwnedTokens(Vec<String>),
 }
 
 impl AttributeValue {
     /// Extracts a string slice representing the contents.
     /// If the value is a `Bool`, this method returns "true" or "false".
-    pub fn as_str(&self) -> &str {
+    ///
+    /// Numbers and token lists have no string representation to borrow, so this method returns
+    /// `None` for them. Use the value's `Display` implementation (such as `to_string`) to format
+    /// any kind of value.
+    pub fn as_str(&self) -> Option<&str> {
         match *self {
-            AttributeValue::Str(value) => value,
+            AttributeValue::Str(value) => Some(value),
+            #[cfg(any(feature = "use_std", test))]
+            AttributeValue::OwnedStr(ref value) => Some(value),
+            AttributeValue::Bool(true) => Some("true"),
+            AttributeValue::Bool(false) => Some("false"),
+            AttributeValue::Int(_) |
+            AttributeValue::Float(_) |
+            AttributeValue::Tokens(_) => None,
             #[cfg(any(feature = "use_std", test))]
-            AttributeValue::OwnedStr(ref value) => value,
-            AttributeValue::Bool(true) => "true",
-            AttributeValue::Bool(false) => "false",
+            AttributeValue::OwnedTokens(_) => None,
         }
     }
 }
 
-#[cfg(any(feature = "use_std", test))]
-impl std::fmt::Display for AttributeValue {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.write_str(self.as_str())
+impl opt_std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut opt_std::fmt::Formatter) -> opt_std::fmt::Result {
+        fn write_tokens<T: AsRef<str>>(f: &mut opt_std::fmt::Formatter, tokens: &[T])
+            -> opt_std::fmt::Result
+        {
+            for (i, token) in tokens.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                f.write_str(token.as_ref())?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            AttributeValue::Str(value) => f.write_str(value),
+            #[cfg(any(feature = "use_std", test))]
+            AttributeValue::OwnedStr(ref value) => f.write_str(value),
+            AttributeValue::Bool(value) => write!(f, "{}", value),
+            AttributeValue::Int(value) => write!(f, "{}", value),
+            AttributeValue::Float(value) => write!(f, "{}", value),
+            AttributeValue::Tokens(tokens) => write_tokens(f, tokens),
+            #[cfg(any(feature = "use_std", test))]
+            AttributeValue::OwnedTokens(ref tokens) => write_tokens(f, tokens),
+        }
+    }
+}
+
+// `Float`s are compared and hashed by their bit patterns so that values can be used as keys and
+// compared when diffing. Values which would be written differently (such as `0.0` and `-0.0`)
+// are never equal.
+impl PartialEq for AttributeValue {
+    fn eq(&self, other: &AttributeValue) -> bool {
+        use AttributeValue::*;
+        match (self, other) {
+            (Str(a), Str(b)) => a == b,
+            #[cfg(any(feature = "use_std", test))]
+            (OwnedStr(a), OwnedStr(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (Int(a), Int(b)) => a == b,
+            (Float(a), Float(b)) => a.to_bits() == b.to_bits(),
+            (Tokens(a), Tokens(b)) => a == b,
+            #[cfg(any(feature = "use_std", test))]
+            (OwnedTokens(a), OwnedTokens(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AttributeValue {}
+
+impl opt_std::hash::Hash for AttributeValue {
+    fn hash<H: opt_std::hash::Hasher>(&self, state: &mut H) {
+        use AttributeValue::*;
+        opt_std::mem::discriminant(self).hash(state);
+        match *self {
+            Str(value) => value.hash(state),
+            #[cfg(any(feature = "use_std", test))]
+            OwnedStr(ref value) => value.hash(state),
+            Bool(value) => value.hash(state),
+            Int(value) => value.hash(state),
+            Float(value) => value.to_bits().hash(state),
+            Tokens(tokens) => tokens.hash(state),
+            #[cfg(any(feature = "use_std", test))]
+            OwnedTokens(ref tokens) => tokens.hash(state),
+        }
     }
 }
 
@@ -205,10 +324,13 @@ mod opt_std {
 
 #[cfg(test)]
 mod tests {
-    use super::{DomNode, DomNodes, DomValue, KeyValue};
+    use super::{DomNode, DomNodes, DomValue, Event, Listener, Listeners, Namespace};
+    use super::{AttributeValue, raw_html};
+    use super::boxed::BoxedNode;
     use super::AttributeValue::Str;
     use super::tags::*;
-    use super::processors::{DomNodeProcessor, EmptyListeners};
+    use super::listener::{on, map_listeners};
+    use super::processors::{DomNodeProcessor, EmptyListeners, ListenerProcessor};
 
     #[cfg(feature = "use_either_n")]
     extern crate either_n;
@@ -242,9 +364,9 @@ mod tests {
         }
 
         fn key(&self) -> Option<u32> { None }
-        fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+        fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> { None }
         fn value(&self) -> DomValue {
-            DomValue::Element { tag: "bogus_tag_one" }
+            DomValue::Element { tag: "bogus_tag_one", namespace: Namespace::Html }
         }
     }
 
@@ -260,7 +382,7 @@ mod tests {
         type WithoutListeners = BogusTwo;
 
         fn key(&self) -> Option<u32> { None }
-        fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+        fn get_attribute(&self, _index: usize) -> Option<(&str, &AttributeValue)> { None }
 
         fn children(&self) -> &Self::Children { EMPTY_NODES_REF }
         fn listeners(&self) -> &Self::Listeners { EMPTY_LISTN_REF }
@@ -272,7 +394,7 @@ mod tests {
         }
 
         fn value(&self) -> DomValue {
-            DomValue::Element { tag: "bogus_tag_two" }
+            DomValue::Element { tag: "bogus_tag_two", namespace: Namespace::Html }
         }
     }
 
@@ -293,6 +415,49 @@ mod tests {
     #[derive(Copy, Clone, Debug, Hash, PartialOrd, PartialEq)]
     enum Never {}
 
+    fn test_event() -> Event<'static> {
+        Event {
+            type_str: Some("click"),
+            target_value: None,
+            client_x: 0,
+            client_y: 0,
+            offset_x: 0,
+            offset_y: 0,
+            which_keycode: 0,
+            shift_key: false,
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+        }
+    }
+
+    // Fires `test_event` at every listener in a tree, collecting the resulting messages
+    struct MessageCollector<M>(PhantomData<M>);
+    impl<'a, M> DomNodeProcessor<'a, M> for MessageCollector<M> {
+        type Acc = Vec<M>;
+        type Error = Never;
+
+        fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Never> {
+            fn collect<M, T: DomNode<M>>(messages: &mut Vec<M>, node: &T) -> Result<(), Never> {
+                node.listeners().process_all::<MessageCollector<M>>(messages)?;
+                node.children().process_all::<MessageCollector<M>>(messages)
+            }
+            collect
+        }
+    }
+    impl<'a, M> ListenerProcessor<'a, M> for MessageCollector<M> {
+        type Acc = Vec<M>;
+        type Error = Never;
+
+        fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Never> {
+            fn handle<M, L: Listener<M>>(messages: &mut Vec<M>, listener: &L) -> Result<(), Never> {
+                messages.push(listener.handle_event(test_event()));
+                Ok(())
+            }
+            handle
+        }
+    }
+
     fn html_sample() -> impl DomNode<Never> + 'static {
         div ((
             attributes([("attr", Str("value"))]),
@@ -428,15 +593,15 @@ mod tests {
     }
 
     fn check_attribute_list<M, T: DomNode<M>>(div: T) {
-        assert_eq!(div.get_attribute(0), Some(&("attr1", Str("val1"))));
-        assert_eq!(div.get_attribute(1), Some(&("attr2", Str("val2"))));
-        assert_eq!(div.get_attribute(2), Some(&("attr3", Str("val3"))));
+        assert_eq!(div.get_attribute(0), Some(("attr1", &Str("val1"))));
+        assert_eq!(div.get_attribute(1), Some(("attr2", &Str("val2"))));
+        assert_eq!(div.get_attribute(2), Some(("attr3", &Str("val3"))));
         assert_eq!(div.get_attribute(3), None);
 
         let mut attr_iter = div.attributes();
-        assert_eq!(attr_iter.next(), Some(&("attr1", Str("val1"))));
-        assert_eq!(attr_iter.next(), Some(&("attr2", Str("val2"))));
-        assert_eq!(attr_iter.next(), Some(&("attr3", Str("val3"))));
+        assert_eq!(attr_iter.next(), Some(("attr1", &Str("val1"))));
+        assert_eq!(attr_iter.next(), Some(("attr2", &Str("val2"))));
+        assert_eq!(attr_iter.next(), Some(("attr3", &Str("val3"))));
         assert_eq!(attr_iter.next(), None);
     }
 
@@ -453,4 +618,1224 @@ mod tests {
         )).with_attributes([("attr1", Str("val1"))]);
         check_attribute_list(div2);
     }
+
+    #[derive(Debug, PartialEq)]
+    enum ChildMsg { Clicked, Hovered }
+
+    #[derive(Debug, PartialEq)]
+    enum ParentMsg { Child(ChildMsg), Other }
+
+    #[test]
+    fn maps_messages() {
+        let child = div((
+            (on("click", |_| ChildMsg::Clicked), on("mouseover", |_| ChildMsg::Hovered)),
+            span(on("click", |_| ChildMsg::Clicked)),
+        ));
+        let parent = div((
+            on("click", |_| ParentMsg::Other),
+            (
+                child.map_messages(ParentMsg::Child),
+                "text",
+            )
+        ));
+
+        let mut messages = Vec::new();
+        parent.process_all::<MessageCollector<ParentMsg>>(&mut messages).unwrap();
+        assert_eq!(vec![
+            ParentMsg::Other,
+            ParentMsg::Child(ChildMsg::Clicked),
+            ParentMsg::Child(ChildMsg::Hovered),
+            ParentMsg::Child(ChildMsg::Clicked),
+        ], messages);
+
+        assert_eq!(
+            "<div><div><span></span></div>text</div>".to_string(),
+            parent.displayable().to_string()
+        );
+    }
+
+    #[test]
+    fn maps_messages_of_split_listeners() {
+        let mapped = div(span(on("click", |_| ChildMsg::Hovered)))
+            .map_messages(ParentMsg::Child)
+            .with_listeners(map_listeners(on("click", |_| ChildMsg::Clicked), ParentMsg::Child))
+            .map_messages(|msg| vec![msg]);
+
+        let mut messages = Vec::new();
+        mapped.process_all::<MessageCollector<Vec<ParentMsg>>>(&mut messages).unwrap();
+        assert_eq!(vec![
+            vec![ParentMsg::Child(ChildMsg::Clicked)],
+            vec![ParentMsg::Child(ChildMsg::Hovered)],
+        ], messages);
+    }
+
+    #[test]
+    fn boxes_recursive_views() {
+        struct Tree(&'static str, Vec<Tree>);
+
+        fn render(tree: &Tree) -> BoxedNode<&'static str> {
+            let name = tree.0;
+            li((
+                on("click", move |_| name),
+                (name, ul(tree.1.iter().map(render).collect::<Vec<_>>())),
+            )).with_key(tree.1.len()).boxed()
+        }
+
+        let tree = Tree("a", vec![
+            Tree("b", vec![Tree("c", vec![])]),
+            Tree("d", vec![]),
+        ]);
+        let rendered = render(&tree);
+
+        assert_eq!(Some(2), rendered.key());
+        assert_eq!(
+            "<li>a<ul><li>b<ul><li>c<ul></ul></li></ul></li><li>d<ul></ul></li></ul></li>",
+            rendered.to_string()
+        );
+
+        let mut messages = Vec::new();
+        rendered.process_all::<MessageCollector<&'static str>>(&mut messages).unwrap();
+        assert_eq!(vec!["a", "b", "c", "d"], messages);
+    }
+
+    #[test]
+    fn boxes_heterogeneous_nodes() {
+        let nodes: Vec<BoxedNode<ParentMsg>> = vec![
+            div(span(on("click", |_| ChildMsg::Clicked))).map_messages(ParentMsg::Child).boxed(),
+            "text".boxed(),
+            button((
+                attributes([("disabled", AttributeValue::Bool(true))]),
+                on("click", |_| ParentMsg::Other),
+            )).boxed()
+                .with_listeners(on("mouseover", |_| ParentMsg::Child(ChildMsg::Hovered)))
+                .boxed(),
+        ];
+        let parent = div(nodes).boxed();
+
+        assert_eq!(
+            "<div><div><span></span></div>text<button disabled></button></div>",
+            parent.to_string()
+        );
+
+        let mut messages = Vec::new();
+        parent.process_all::<MessageCollector<ParentMsg>>(&mut messages).unwrap();
+        assert_eq!(vec![
+            ParentMsg::Child(ChildMsg::Clicked),
+            ParentMsg::Child(ChildMsg::Hovered),
+            ParentMsg::Other,
+        ], messages);
+
+        // Split off listeners still borrow from the original node after the rest is dropped
+        let (without_listeners, listeners) = button(on("click", |_| ParentMsg::Other))
+            .boxed()
+            .split_listeners();
+        assert_eq!("<button></button>", without_listeners.displayable().to_string());
+        drop(without_listeners);
+        let mut messages = Vec::new();
+        listeners.process_all::<MessageCollector<ParentMsg>>(&mut messages).unwrap();
+        assert_eq!(vec![ParentMsg::Other], messages);
+    }
+
+    #[test]
+    fn builds_numeric_and_token_attributes() {
+        let string = div((
+            attributes([
+                ("tabindex", AttributeValue::Int(-1)),
+                ("width", AttributeValue::Int(640)),
+                ("step", AttributeValue::Float(0.25)),
+                ("class", AttributeValue::Tokens(&["button", "primary"])),
+                ("rel", AttributeValue::OwnedTokens(vec!["noopener".into(), "noreferrer".into()])),
+            ]),
+            PhantomData::<Never>,
+        )).displayable().to_string();
+        assert_eq!(
+            r#"<div tabindex="-1" width="640" step="0.25" class="button primary" rel="noopener noreferrer"></div>"#,
+            string
+        );
+    }
+
+    #[test]
+    fn compares_attribute_values_by_value() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash(value: &AttributeValue) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        assert_eq!(AttributeValue::Float(1.5), AttributeValue::Float(1.5));
+        assert_eq!(hash(&AttributeValue::Float(1.5)), hash(&AttributeValue::Float(1.5)));
+        assert!(AttributeValue::Float(0.0) != AttributeValue::Float(-0.0));
+        assert!(AttributeValue::Int(1) != AttributeValue::Float(1.0));
+        assert_eq!(AttributeValue::Tokens(&["a", "b"]), AttributeValue::Tokens(&["a", "b"]));
+        assert!(AttributeValue::Tokens(&["a", "b"]) != AttributeValue::Tokens(&["b", "a"]));
+
+        assert_eq!(Some("value"), AttributeValue::Str("value").as_str());
+        assert_eq!(None, AttributeValue::Int(3).as_str());
+        assert_eq!("3", AttributeValue::Int(3).to_string());
+    }
+
+    #[test]
+    fn builds_custom_tags() {
+        let name = String::from("comment-thread");
+        let string = div((
+            custom_tag(name, (
+                attributes([("depth", AttributeValue::Int(2))]),
+                custom_tag("x-item", "reply"),
+            )),
+            PhantomData::<Never>,
+        )).displayable().to_string();
+        assert_eq!(
+            r#"<div><comment-thread depth="2"><x-item>reply</x-item></comment-thread></div>"#,
+            string
+        );
+    }
+
+    #[test]
+    fn validates_custom_element_names() {
+        assert!(is_valid_custom_element_name("my-date-picker"));
+        assert!(is_valid_custom_element_name("x-"));
+        assert!(is_valid_custom_element_name("math-\u{3B1}"));
+        assert!(is_valid_custom_element_name("a.b_c-1"));
+
+        assert!(!is_valid_custom_element_name(""));
+        assert!(!is_valid_custom_element_name("div"));
+        assert!(!is_valid_custom_element_name("My-element"));
+        assert!(!is_valid_custom_element_name("my-Element"));
+        assert!(!is_valid_custom_element_name("-my-element"));
+        assert!(!is_valid_custom_element_name("1-element"));
+        assert!(!is_valid_custom_element_name("my-element>"));
+        assert!(!is_valid_custom_element_name("my element-x"));
+        assert!(!is_valid_custom_element_name("font-face"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_invalid_custom_tags() {
+        custom_tag("datepicker", PhantomData::<Never>);
+    }
+
+    #[test]
+    fn tries_custom_tags() {
+        let names = ["x-item", "Item", "font-face"];
+        let tags = names.iter()
+            .filter_map(|&name| try_custom_tag(name, "item"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            "<ul><x-item>item</x-item></ul>",
+            ul((tags, PhantomData::<Never>)).displayable().to_string()
+        );
+    }
+
+    #[test]
+    fn builds_svg_and_mathml() {
+        use super::tags::svg::{svg, circle, g, linear_gradient, use_};
+        use super::tags::mathml::{math, mfrac, mi, mn};
+
+        let chart = svg((
+            attributes([("viewBox", Str("0 0 10 10"))]),
+            (
+                linear_gradient(attributes([("id", Str("fill"))])),
+                g((
+                    circle(attributes([("r", AttributeValue::Int(4))])),
+                    use_(attributes([("xlink:href", Str("#fill"))])),
+                )),
+                PhantomData::<Never>,
+            )
+        ));
+        assert_eq!(
+            DomValue::Element { tag: "linearGradient", namespace: Namespace::Svg },
+            linear_gradient(PhantomData::<Never>).value()
+        );
+        assert_eq!(
+            r##"<svg viewBox="0 0 10 10"><linearGradient id="fill" /><g><circle r="4" /><use xlink:href="#fill" /></g></svg>"##,
+            chart.to_string()
+        );
+
+        let fraction = div(math(mfrac((mi("x"), mn("2"), PhantomData::<Never>))));
+        assert_eq!(
+            DomValue::Element { tag: "math", namespace: Namespace::MathMl },
+            math(PhantomData::<Never>).value()
+        );
+        assert_eq!(
+            "<div><math><mfrac><mi>x</mi><mn>2</mn></mfrac></math></div>",
+            fraction.to_string()
+        );
+    }
+
+    #[test]
+    fn writes_raw_html_verbatim() {
+        let markup = "<b>bold</b> &amp; <i>italic</i>";
+        let node: Tag<(), _, _, _> = div((markup, raw_html(markup), raw_html(markup.to_string())));
+
+        assert_eq!(DomValue::RawHtml(markup), DomNode::<()>::value(&raw_html(markup)));
+        assert_eq!(
+            format!(
+                "<div>&lt;b&gt;bold&lt;/b&gt; &amp;amp; &lt;i&gt;italic&lt;/i&gt;{0}{0}</div>",
+                markup
+            ),
+            node.to_string()
+        );
+    }
+
+    #[test]
+    fn writes_void_elements() {
+        let form: Tag<(), _, _, _> = div((
+            input(attributes([("type", Str("text"))])),
+            br(()),
+            img(attributes([("src", Str("a.png"))])),
+            "text",
+        ));
+        assert_eq!(
+            r#"<div><input type="text"><br><img src="a.png">text</div>"#,
+            form.to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_children_of_void_elements() {
+        let invalid: Tag<(), _, _, _> = div(br("text"));
+        let error = invalid.write_html(&mut Vec::new()).unwrap_err();
+        assert_eq!(::std::io::ErrorKind::InvalidInput, error.kind());
+    }
+
+    #[test]
+    fn escapes_attribute_values() {
+        let user_input = "\"><script>alert('&')</script>".to_string();
+        let link: Tag<(), _, _, _> = a(attributes([
+            ("title", AttributeValue::OwnedStr(user_input)),
+            ("href", Str("/search?a=1&b=2")),
+        ]));
+        assert_eq!(
+            "<a title=\"&#34;&gt;&lt;script&gt;alert&#40;&#39;&amp;&#39;&#41;&lt;/script&gt;\" \
+                href=\"/search?a&#61;1&amp;b&#61;2\"></a>",
+            link.to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_attribute_names() {
+        for name in &["", "a b", "a\"", "a>", "a=b", "a/"] {
+            let invalid: Tag<(), _, _, _> = div(attributes([(*name, Str("value"))]));
+            let error = invalid.write_html(&mut Vec::new()).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidInput, error.kind());
+        }
+
+        let valid: Tag<(), _, _, _> =
+            div(attributes([("data-x", Str("1")), ("xlink:href", Str("#a"))]));
+        assert_eq!(r##"<div data-x="1" xlink:href="#a"></div>"##, valid.to_string());
+    }
+
+    #[test]
+    fn writes_boolean_attributes() {
+        let field: Tag<(), _, _, _> = input(attributes([
+            ("type", Str("checkbox")),
+            ("checked", AttributeValue::Bool(true)),
+            ("disabled", AttributeValue::Bool(false)),
+            ("autofocus", AttributeValue::Bool(true)),
+        ]));
+        assert_eq!(r#"<input type="checkbox" checked autofocus>"#, field.to_string());
+    }
+
+    #[test]
+    fn writes_html_to_fmt_write() {
+        let node = html_sample();
+        let mut bytes = Vec::new();
+        node.write_html(&mut bytes).unwrap();
+        let mut string = String::new();
+        node.write_html_fmt(&mut string).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), string);
+
+        let escaped: Tag<(), _, _, _> = p((
+            attributes([("data-n", AttributeValue::Float(1.5)), ("title", Str("\"é\" & ü"))]),
+            "<héllo> wörld!",
+        ));
+        string.clear();
+        escaped.write_html_fmt(&mut string).unwrap();
+        assert_eq!(
+            "<p data-n=\"1.5\" title=\"&#34;é&#34; &amp; ü\">&lt;héllo&gt; wörld&#33;</p>",
+            string
+        );
+
+        let invalid: Tag<(), _, _, _> = div(br("text"));
+        assert!(invalid.write_html_fmt(&mut String::new()).is_err());
+    }
+
+    #[test]
+    fn writes_formatted_html() {
+        use super::html_writer::{Escaping, HtmlFormat};
+
+        let page: Tag<(), _, _, _> = div((
+            attributes([("title", Str("\"Q&A\" <1>"))]),
+            (
+                ul((li("one"), li(("two ", a("link"))))),
+                "after list",
+                span("inline"),
+                pre("  keep\n  this"),
+                p(()),
+            ),
+        ));
+
+        let mut pretty = String::new();
+        HtmlFormat::pretty(4).write_html_fmt(&page, &mut pretty).unwrap();
+        assert_eq!(
+            "<div title=\"&#34;Q&amp;A&#34; &lt;1&gt;\">\n\
+            \x20   <ul>\n\
+            \x20       <li>one</li>\n\
+            \x20       <li>two <a>link</a></li>\n\
+            \x20   </ul>\n\
+            \x20   after list<span>inline</span>\n\
+            \x20   <pre>  keep\n  this</pre>\n\
+            \x20   <p></p>\n\
+            </div>",
+            pretty
+        );
+
+        let mut minified = Vec::new();
+        HtmlFormat::minified().write_html(&page, &mut minified).unwrap();
+        assert_eq!(
+            "<div title=\"&quot;Q&amp;A&quot; <1>\"><ul><li>one</li><li>two <a>link</a></li></ul>\
+            after list<span>inline</span><pre>  keep\n  this</pre><p></p></div>",
+            String::from_utf8(minified).unwrap()
+        );
+
+        let text: Tag<(), _, _, _> = p("a < b && c > d!");
+        let mut pretty_minimal = String::new();
+        HtmlFormat::pretty(1).with_escaping(Escaping::Minimal)
+            .write_html_fmt(&text, &mut pretty_minimal).unwrap();
+        assert_eq!("<p>a &lt; b &amp;&amp; c > d!</p>", pretty_minimal);
+
+        let mut default = String::new();
+        HtmlFormat::new().write_html_fmt(&page, &mut default).unwrap();
+        assert_eq!(page.to_string(), default);
+
+        // Inline SVG and MathML stay on the line of the surrounding text
+        use super::tags::mathml::{math, mi};
+        use super::tags::svg::{circle, svg};
+        let icon: Tag<(), _, _, _> = p((
+            span(("Saved ", svg(circle(())))),
+            math(mi("x")),
+        ));
+        let mut pretty_icon = String::new();
+        HtmlFormat::pretty(2).write_html_fmt(&icon, &mut pretty_icon).unwrap();
+        assert_eq!(
+            "<p><span>Saved <svg><circle /></svg></span><math><mi>x</mi></math></p>",
+            pretty_icon
+        );
+    }
+
+    #[test]
+    fn writes_documents() {
+        use super::document::Document;
+        use super::html_writer::HtmlFormat;
+
+        let page: Tag<(), _, _, _> = body((
+            head((title("Outer"), meta(attributes([("charset", Str("utf-8"))])))),
+            div((
+                title("Inner & last"),
+                p("content"),
+                script(attributes([("src", Str("/app.js"))])),
+            )),
+        ));
+        let document = Document::new(page).with_title("Default").with_format(HtmlFormat::pretty(2));
+        assert_eq!(
+            "<!DOCTYPE html>\n\
+            <html>\n\
+            \x20 <head>\n\
+            \x20   <meta charset=\"utf-8\">\n\
+            \x20   <title>Inner &amp; last</title>\n\
+            \x20   <script src=\"/app.js\"></script>\n\
+            \x20 </head>\n\
+            \x20 <body>\n\
+            \x20   <div>\n\
+            \x20     <p>content</p>\n\
+            \x20   </div>\n\
+            \x20 </body>\n\
+            </html>",
+            document.to_string()
+        );
+
+        let untitled: Document<(), _> = Document::new("text").with_title("<Default>");
+        let mut bytes = Vec::new();
+        untitled.write_html(&mut bytes).unwrap();
+        assert_eq!(
+            "<!DOCTYPE html><html><head><title>&lt;Default&gt;</title></head>\
+            <body>text</body></html>",
+            String::from_utf8(bytes).unwrap()
+        );
+    }
+
+    #[test]
+    fn streams_html_in_chunks() {
+        use super::document::Document;
+        use super::flush_point;
+        use super::html_writer::{FlushPolicy, HtmlFormat};
+        use std::io::{self, Write};
+
+        // Records the output written between each flush
+        struct Chunks(Vec<String>, Vec<u8>);
+        impl Write for Chunks {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                if !self.1.is_empty() {
+                    self.0.push(String::from_utf8(self.1.split_off(0)).unwrap());
+                }
+                Ok(())
+            }
+        }
+
+        let page: Tag<(), _, _, _> = div((
+            head(title("Results")),
+            h1("Results"),
+            flush_point().with_key(1),
+            ul(li("first").map_messages(|()| ()).boxed()),
+            flush_point(),
+            p("done"),
+        ));
+
+        let mut chunks = Chunks(Vec::new(), Vec::new());
+        let policy = FlushPolicy::new().with_flush_points(false);
+        HtmlFormat::pretty(2).stream_html(&page, &mut chunks, policy).unwrap();
+        assert_eq!(
+            vec![
+                "<div>\n  <head>\n    <title>Results</title>\n  </head>",
+                "\n  <h1>Results</h1>\n  <ul>\n    <li>first</li>\n  </ul>\n  <p>done</p>\n</div>",
+            ],
+            chunks.0
+        );
+
+        let mut chunks = Chunks(Vec::new(), Vec::new());
+        Document::new(page).stream_html(&mut chunks, FlushPolicy::new()).unwrap();
+        assert_eq!(
+            vec![
+                "<!DOCTYPE html><html><head><title>Results</title></head>",
+                "<body><div><h1>Results</h1>",
+                "<ul><li>first</li></ul>",
+                "<p>done</p></div></body></html>",
+            ],
+            chunks.0
+        );
+    }
+
+    #[test]
+    fn skips_flush_points_when_rendering() {
+        use super::diff::Reconciler;
+        use super::flush_point;
+        use super::mock_dom::MockDom;
+
+        let page = ul((
+            flush_point(),
+            li((on("click", |_| 1), "first")),
+            li((on("click", |_| 2), (flush_point(), "second"))),
+        ));
+        assert_eq!("<ul><li>first</li><li>second</li></ul>", page.displayable().to_string());
+
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(page);
+        let dom = reconciler.backend();
+        assert_eq!("<ul><li>first</li><li>second</li></ul>", dom.to_string());
+        let items = dom.children(dom.children(root)[0]).to_vec();
+        assert_eq!(2, items.len());
+        assert_eq!(1, dom.children(items[1]).len());
+        assert_eq!(1, dom.dispatch(items[0], "click").unwrap().0);
+        assert_eq!(2, dom.dispatch(items[1], "click").unwrap().0);
+    }
+
+    #[test]
+    fn parses_html() {
+        use super::html_writer::HtmlFormat;
+        use super::parser::{ParseErrorKind, parse_html};
+
+        let html = "<!DOCTYPE html>\n<div id=main data-x='a &lt; b'>\
+            <!-- note --><input disabled><br/>\
+            <p>1 < 2 &copy; &#x263A; &bogus;</p>\
+            <svg viewBox=\"0 0 1 1\"><foreignObject><B>x</B></foreignObject></svg>\
+            <script>if (a < b) {}</script><textarea>&lt;b&gt;</textarea>\
+            </DIV>";
+        let nodes = parse_html(html).unwrap();
+        assert_eq!(2, nodes.len());
+        assert_eq!(DomValue::Text("\n"), nodes[0].value());
+
+        let div = &nodes[1];
+        assert_eq!(DomValue::Element { tag: "div", namespace: Namespace::Html }, div.value());
+        assert_eq!(Some("a < b"), div.attributes()[1].1.as_str());
+        let svg = &div.children()[4];
+        assert_eq!(DomValue::Element { tag: "svg", namespace: Namespace::Svg }, svg.value());
+        assert_eq!("viewBox", svg.attributes()[0].0);
+        assert_eq!(
+            DomValue::Element { tag: "b", namespace: Namespace::Html },
+            svg.children()[0].children()[0].value()
+        );
+
+        let page: Tag<(), _, _, _> = body(nodes);
+        let mut written = String::new();
+        HtmlFormat::minified().write_html_fmt(&page, &mut written).unwrap();
+        assert_eq!(
+            "<body>\n<div id=\"main\" data-x=\"a < b\"><!-- note --><input disabled><br>\
+            <p>1 &lt; 2 \u{A9} \u{263A} &amp;bogus;</p>\
+            <svg viewBox=\"0 0 1 1\"><foreignObject><b>x</b></foreignObject></svg>\
+            <script>if (a < b) {}</script><textarea>&lt;b></textarea></div></body>",
+            written
+        );
+
+        let errors = [
+            ("<div>", 1, 1, ParseErrorKind::UnclosedElement("div".to_string())),
+            ("<div>\n<p></div>", 2, 4, ParseErrorKind::MismatchedClosingTag {
+                expected: "p".to_string(),
+                found: "div".to_string(),
+            }),
+            ("</p>", 1, 1, ParseErrorKind::UnexpectedClosingTag("p".to_string())),
+            ("<br></br>", 1, 5, ParseErrorKind::ClosingVoidElement("br".to_string())),
+            ("<a href=\"x\" href=\"y\">", 1, 13,
+                ParseErrorKind::DuplicateAttribute("href".to_string())),
+            ("<a href=>", 1, 9, ParseErrorKind::MissingAttributeValue("href".to_string())),
+            ("<a title=\"&#xD800;\">", 1, 11,
+                ParseErrorKind::InvalidCharacterReference("&#xD800;".to_string())),
+            ("<a b\"c>", 1, 4, ParseErrorKind::InvalidAttributeName("b\"c".to_string())),
+            ("<a href=\"x", 1, 11, ParseErrorKind::UnexpectedEof),
+            ("<!-- x", 1, 1, ParseErrorKind::UnclosedComment),
+            ("<a+>", 1, 3, ParseErrorKind::UnexpectedCharacter('+')),
+        ];
+        for &(html, line, column, ref kind) in errors.iter() {
+            let error = parse_html(html).unwrap_err();
+            assert_eq!(
+                (line, column, kind),
+                (error.line(), error.column(), error.kind()),
+                "{}", html
+            );
+        }
+    }
+
+    #[test]
+    fn converts_nodes_to_vnodes() {
+        use super::vnode::{VNode, VNodeBuilder};
+
+        let list: Tag<(), _, _, _> = ul((
+            attributes([("class", Str("items"))]),
+            (
+                li((on("click", |_| ()), "first")).with_key(1),
+                li(raw_html("<b>second</b>")).with_key(2).boxed(),
+            ),
+        ));
+        let vnode = VNode::from_node(&list);
+        assert_eq!(
+            VNode::Element {
+                tag: "ul".to_string(),
+                namespace: Namespace::Html,
+                key: None,
+                attributes: vec![("class".to_string(), Str("items"))],
+                properties: vec![],
+                children: vec![
+                    VNode::Element {
+                        tag: "li".to_string(),
+                        namespace: Namespace::Html,
+                        key: Some(1),
+                        attributes: vec![],
+                        properties: vec![],
+                        children: vec![VNode::Text("first".to_string())],
+                    },
+                    VNode::Element {
+                        tag: "li".to_string(),
+                        namespace: Namespace::Html,
+                        key: Some(2),
+                        attributes: vec![],
+                        properties: vec![],
+                        children: vec![VNode::RawHtml("<b>second</b>".to_string())],
+                    },
+                ],
+            },
+            vnode
+        );
+        assert_eq!(list.to_string(), vnode.to_string());
+
+        // `VNode`s are `DomNode`s themselves, so they convert to equal `VNode`s
+        let mut nodes = Vec::new();
+        DomNodes::<()>::process_all::<VNodeBuilder>(&(vnode.clone(), "text"), &mut nodes).unwrap();
+        assert_eq!(vec![vnode, VNode::Text("text".to_string())], nodes);
+    }
+
+    #[test]
+    fn reconciles_mock_dom() {
+        use super::diff::Reconciler;
+        use super::mock_dom::{MockDom, MockOperation};
+        use super::vnode::VNode;
+
+        let render = |items: &[usize], class: &'static str| ul((
+            attributes([("class", Str(class))]),
+            items.iter().map(|&item|
+                li((on("click", move |_| item), item.to_string())).with_key(item)
+            ).collect::<Vec<_>>(),
+        ));
+        let is_create = |op: &MockOperation| matches!(*op, MockOperation::Create(_));
+
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(render(&[1, 2, 3], "first"));
+        let (list, items) = {
+            let dom = reconciler.backend();
+            assert_eq!(
+                "<ul class=\"first\"><li>1</li><li>2</li><li>3</li></ul>",
+                dom.to_string()
+            );
+            let list = dom.children(root)[0];
+            (list, dom.children(list).to_vec())
+        };
+        let (message, keys) = reconciler.backend().dispatch(items[1], "click").unwrap();
+        assert_eq!(2, message);
+        assert_eq!(vec![2], keys.collect::<Vec<_>>());
+        reconciler.backend().take_operations();
+
+        // Keyed nodes are moved rather than recreated
+        reconciler.render(render(&[3, 1], "second"));
+        {
+            let dom = reconciler.backend();
+            let operations = dom.take_operations();
+            assert!(!operations.iter().any(is_create));
+            assert!(operations.contains(&MockOperation::SetAttribute(list, "class".to_string())));
+            assert!(!operations.iter().any(|op| matches!(*op, MockOperation::RemoveAttribute(..))));
+            assert!(operations.contains(&MockOperation::Remove { parent: list, child: items[1] }));
+            assert_eq!("<ul class=\"second\"><li>3</li><li>1</li></ul>", dom.to_string());
+            assert_eq!(&[items[2], items[0]], dom.children(list));
+            assert_eq!(None, dom.parent(items[1]));
+            assert!(dom.dispatch(items[1], "click").is_none());
+            assert_eq!(3, dom.dispatch(items[2], "click").unwrap().0);
+        }
+
+        // Changed text is updated in place
+        let counter =
+            |count: usize| p((on("click", move |_| count), ("Count: ", count.to_string())));
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut counter_reconciler = Reconciler::new(dom, root);
+        counter_reconciler.render(counter(1));
+        counter_reconciler.backend().take_operations();
+        counter_reconciler.render(counter(2));
+        {
+            let dom = counter_reconciler.backend();
+            let paragraph = dom.children(root)[0];
+            let count_text = dom.children(paragraph)[1];
+            let operations = dom.take_operations();
+            assert!(!operations.iter().any(is_create));
+            assert!(operations.contains(&MockOperation::SetText(count_text)));
+            assert!(!operations.iter().any(|op| matches!(*op, MockOperation::Remove { .. })));
+            assert_eq!("<p>Count: 2</p>", dom.to_string());
+            assert_eq!(2, dom.dispatch(paragraph, "click").unwrap().0);
+        }
+
+        // Hydration adopts existing nodes until they stop matching. Server-rendered HTML has no
+        // keys, so the second `li` is adopted and only its text is replaced.
+        let server_html = VNode::from_node(&render(&[1, 2], "first"));
+        let dom = MockDom::with_children(&[server_html]);
+        let root = dom.root();
+        let (list, items) = {
+            let list = dom.children(root)[0];
+            (list, dom.children(list).to_vec())
+        };
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.hydrate(render(&[1, 3], "first"));
+        let dom = reconciler.backend();
+        let operations = dom.take_operations();
+        assert!(operations.contains(&MockOperation::Claim(items[0])));
+        assert!(operations.contains(&MockOperation::Claim(items[1])));
+        assert!(operations.contains(
+            &MockOperation::HydrationMismatch { parent: items[1], index: 0 }));
+        assert_eq!(1, operations.iter().filter(|op| is_create(op)).count());
+        assert_eq!(list, dom.children(root)[0]);
+        assert_eq!(&items[..], dom.children(list));
+        assert_eq!("<ul class=\"first\"><li>1</li><li>3</li></ul>", dom.to_string());
+        assert_eq!(1, dom.dispatch(items[0], "click").unwrap().0);
+
+        // Elements with different attributes are adopted, but the difference is reported
+        let server_html = VNode::from_node(&render(&[1], "first"));
+        let dom = MockDom::with_children(&[server_html]);
+        let root = dom.root();
+        let list = dom.children(root)[0];
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.hydrate(render(&[1], "second"));
+        let dom = reconciler.backend();
+        let operations = dom.take_operations();
+        assert!(operations.contains(&MockOperation::Claim(list)));
+        assert_eq!(
+            vec![&MockOperation::HydrationMismatch { parent: root, index: 0 }],
+            operations.iter()
+                .filter(|op| matches!(**op, MockOperation::HydrationMismatch { .. }))
+                .collect::<Vec<_>>()
+        );
+        assert!(!operations.iter().any(is_create));
+        assert_eq!("<ul class=\"second\"><li>1</li></ul>", dom.to_string());
+
+        // Server-rendered attributes that the first render doesn't have are removed
+        let render = |class: &'static str| div(attributes([("class", Str(class))]));
+        let server_html = VNode::from_node::<(), _>(
+            &div(attributes([("class", Str("a")), ("data-stale", Str("x"))])));
+        let dom = MockDom::<()>::with_children(&[server_html]);
+        let root = dom.root();
+        let element = dom.children(root)[0];
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.hydrate(render("a"));
+        let operations = reconciler.backend().take_operations();
+        assert!(operations.contains(&MockOperation::Claim(element)));
+        assert!(operations.contains(
+            &MockOperation::RemoveAttribute(element, "data-stale".to_string())));
+        reconciler.render(render("b"));
+        assert_eq!("<div class=\"b\"></div>", reconciler.backend().to_string());
+    }
+
+    #[test]
+    fn reuses_listeners() {
+        use super::diff::Reconciler;
+        use super::mock_dom::{MockDom, MockOperation};
+
+        // Every render creates new listeners, which are swapped in without touching the DOM
+        let render = |count: usize, event_type: &'static str| div((
+            on("click", move |_| (0, count)),
+            ul((0..3).map(|item|
+                li((on(event_type, move |_| (item, count)), raw_html("<b>item</b>")))
+                    .with_key(item)
+            ).collect::<Vec<_>>()),
+        ));
+        let is_listener_op = |op: &MockOperation| matches!(*op,
+            MockOperation::AddListener(..) | MockOperation::RemoveListener(..));
+
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(render(1, "click"));
+        assert_eq!(4, reconciler.backend().take_operations().into_iter()
+            .filter(|op| is_listener_op(op)).count());
+
+        reconciler.render(render(2, "click"));
+        let item = {
+            let dom = reconciler.backend();
+            assert!(!dom.take_operations().iter().any(is_listener_op));
+            let list = dom.children(dom.children(root)[0])[0];
+            dom.children(list)[1]
+        };
+        let (message, keys) = reconciler.backend().dispatch(item, "click").unwrap();
+        assert_eq!((1, 2), message);
+        assert_eq!(vec![1], keys.collect::<Vec<_>>());
+
+        // Events are routed by path, from the target up through its ancestors if they bubble
+        let dispatch = |reconciler: &Reconciler<_, _, MockDom<_>>, path: &[usize], bubbles| {
+            reconciler.dispatch(path, test_event(), bubbles).into_iter()
+                .map(|(message, keys)| (message, keys.collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![((1, 2), vec![1]), ((0, 2), vec![])],
+            dispatch(&reconciler, &[0, 0, 1], true)
+        );
+        assert_eq!(vec![((1, 2), vec![1])], dispatch(&reconciler, &[0, 0, 1], false));
+        assert_eq!(vec![((0, 2), vec![])], dispatch(&reconciler, &[0, 0], true));
+        assert!(dispatch(&reconciler, &[0, 0], false).is_empty());
+        // Events from inside raw HTML start from the node containing it
+        assert_eq!(
+            vec![((2, 2), vec![2]), ((0, 2), vec![])],
+            dispatch(&reconciler, &[0, 0, 2, 0, 0], true)
+        );
+        assert!(dispatch(&reconciler, &[0, 0, 2, 0, 0], false).is_empty());
+        assert!(dispatch(&reconciler, &[1], true).is_empty());
+
+        // Listeners are only replaced when their event type changes
+        reconciler.render(render(3, "mouseover"));
+        let dom = reconciler.backend();
+        let operations = dom.take_operations();
+        assert_eq!(3, operations.iter()
+            .filter(|op| matches!(**op, MockOperation::RemoveListener(_, "click"))).count());
+        assert_eq!(3, operations.iter()
+            .filter(|op| matches!(**op, MockOperation::AddListener(_, "mouseover"))).count());
+        assert_eq!(6, operations.len());
+        assert_eq!((1, 3), dom.dispatch(item, "mouseover").unwrap().0);
+        assert!(dom.dispatch(item, "click").is_none());
+    }
+
+    #[test]
+    fn reorders_keyed_lists() {
+        use super::diff::Reconciler;
+        use super::mock_dom::{MockDom, MockOperation};
+
+        let render = |items: &[usize]| -> Tag<(), _, _, _> {
+            ul(items.iter().map(|&item| li(item.to_string()).with_key(item)).collect::<Vec<_>>())
+        };
+        let html = |items: &[usize]| {
+            let items = items.iter().map(|item| format!("<li>{}</li>", item)).collect::<String>();
+            format!("<ul>{}</ul>", items)
+        };
+        // Length of the longest increasing subsequence, the most nodes that can stay in place
+        let longest_increasing = |items: &[usize]| {
+            let mut lengths = vec![1; items.len()];
+            for i in 0..items.len() {
+                for j in 0..i {
+                    if items[j] < items[i] && lengths[j] + 1 > lengths[i] {
+                        lengths[i] = lengths[j] + 1;
+                    }
+                }
+            }
+            lengths.into_iter().max().unwrap_or(0)
+        };
+
+        // Every permutation of five items, reached from the sorted list with the fewest moves
+        let mut permutations = vec![vec![]];
+        for item in 0..5 {
+            permutations = permutations.into_iter().flat_map(|permutation: Vec<usize>|
+                (0..permutation.len() + 1).map(move |index| {
+                    let mut permutation = permutation.clone();
+                    permutation.insert(index, item);
+                    permutation
+                })
+            ).collect();
+        }
+        assert_eq!(120, permutations.len());
+        for permutation in permutations {
+            let dom = MockDom::new();
+            let root = dom.root();
+            let mut reconciler = Reconciler::new(dom, root);
+            reconciler.render(render(&[0, 1, 2, 3, 4]));
+            let (list, items) = {
+                let dom = reconciler.backend();
+                let list = dom.children(root)[0];
+                dom.take_operations();
+                (list, dom.children(list).to_vec())
+            };
+
+            reconciler.render(render(&permutation));
+            let dom = reconciler.backend();
+            let operations = dom.take_operations();
+            assert_eq!(html(&permutation), dom.to_string());
+            assert_eq!(
+                permutation.iter().map(|&item| items[item]).collect::<Vec<_>>(),
+                dom.children(list)
+            );
+            assert!(operations.iter().all(|op| matches!(*op, MockOperation::Move { .. })));
+            assert_eq!(5 - longest_increasing(&permutation), operations.len());
+        }
+
+        // Items are added, removed and moved at the same time
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        let mut items = vec![0, 1, 2, 3, 4, 5];
+        reconciler.render(render(&items));
+        let mut seed = 7u32;
+        for _ in 0..200 {
+            let mut next_items = Vec::new();
+            for _ in 0..12 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let item = ((seed >> 16) % 16) as usize;
+                if !next_items.contains(&item) {
+                    next_items.push(item);
+                }
+            }
+
+            let old_nodes = {
+                let dom = reconciler.backend();
+                let list = dom.children(root)[0];
+                dom.take_operations();
+                items.iter().cloned().zip(dom.children(list).iter().cloned()).collect::<Vec<_>>()
+            };
+            reconciler.render(render(&next_items));
+            let dom = reconciler.backend();
+            assert_eq!(html(&next_items), dom.to_string());
+
+            // Kept items are the same nodes, and only the ones out of order are moved
+            let list = dom.children(root)[0];
+            let kept = next_items.iter().filter_map(|item|
+                old_nodes.iter().position(|&(old_item, _)| old_item == *item).inspect(|&index| {
+                    assert_eq!(old_nodes[index].1, dom.children(list)[next_items.iter()
+                        .position(|next_item| next_item == item).unwrap()]);
+                })
+            ).collect::<Vec<_>>();
+            let moves = dom.take_operations().into_iter()
+                .filter(|op| matches!(*op, MockOperation::Move { .. }))
+                .count();
+            assert_eq!(kept.len() - longest_increasing(&kept), moves);
+            items = next_items;
+        }
+    }
+
+    #[test]
+    fn diffs_attributes_by_name() {
+        use super::diff::Reconciler;
+        use super::mock_dom::{MockDom, MockOperation};
+
+        let render = |id: &'static str, classes: &[&'static str]| -> Tag<(), _, _, _> {
+            div(attributes(Some(("id", Str(id))).into_iter()
+                .chain(classes.iter().map(|&class| ("class", Str(class))))
+                .collect::<Vec<_>>()))
+        };
+
+        // The last attribute with a repeated name is used, both when rendering to HTML and to a DOM
+        assert_eq!("<div id=\"a\" class=\"y\"></div>", render("a", &["x", "y"]).to_string());
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(render("a", &["x", "y"]));
+        let node = reconciler.backend().children(root)[0];
+        assert_eq!(
+            &[("class".to_string(), Str("y")), ("id".to_string(), Str("a"))],
+            reconciler.backend().attributes(node)
+        );
+        reconciler.backend().take_operations();
+
+        // A changed value is set once, without removing the attribute first
+        reconciler.render(render("b", &["x", "y"]));
+        assert_eq!(
+            vec![MockOperation::SetAttribute(node, "id".to_string())],
+            reconciler.backend().take_operations()
+        );
+
+        // Reordering attributes or repeating a value that's already set changes nothing
+        reconciler.render(render("b", &["y", "y"]));
+        assert_eq!(Vec::<MockOperation>::new(), reconciler.backend().take_operations());
+
+        reconciler.render(render("b", &[]));
+        assert_eq!(
+            vec![MockOperation::RemoveAttribute(node, "class".to_string())],
+            reconciler.backend().take_operations()
+        );
+        assert_eq!("<div id=\"b\"></div>", reconciler.backend().to_string());
+    }
+
+    #[test]
+    fn sets_dom_properties() {
+        use super::diff::Reconciler;
+        use super::mock_dom::{MockDom, MockOperation};
+        use super::patch::{diff, NodeId, Patch};
+        use super::vnode::VNode;
+
+        // `None` leaves out the property
+        let render = |value: Option<&'static str>, checked: Option<bool>| -> Tag<(), _, _, _> {
+            div((
+                input(attributes([("type", Str("text")), ("value", Str("default"))]))
+                    .with_properties(value.map(|value| ("value", Str(value))).into_iter()
+                        .collect::<Vec<_>>()),
+                input(attributes([("type", Str("checkbox"))]))
+                    .with_properties(checked.map(|checked| ("checked", AttributeValue::Bool(checked)))
+                        .into_iter().collect::<Vec<_>>()),
+            ))
+        };
+
+        // Properties are written as attributes, and take precedence over attributes
+        assert_eq!(
+            "<div><input type=\"text\" value=\"a\"><input type=\"checkbox\"></div>",
+            render(Some("a"), Some(false)).to_string()
+        );
+        let vnode = VNode::from_node(&render(Some("a"), Some(false)).boxed());
+        let text_input = &DomNode::<()>::children(&vnode)[0];
+        assert_eq!(
+            vec![&("value", Str("a"))],
+            DomNode::<()>::properties(text_input).collect::<Vec<_>>()
+        );
+
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(render(Some("a"), Some(false)));
+        let (text, checkbox) = {
+            let dom = reconciler.backend();
+            let children = dom.children(dom.children(root)[0]);
+            (children[0], children[1])
+        };
+        assert_eq!(&[("value", Str("a"))], reconciler.backend().properties(text));
+        assert_eq!(
+            &[("checked", AttributeValue::Bool(false))],
+            reconciler.backend().properties(checkbox)
+        );
+        reconciler.backend().take_operations();
+
+        // Unchanged properties are left alone, unless the user has changed them
+        reconciler.render(render(Some("a"), Some(false)));
+        assert_eq!(Vec::<MockOperation>::new(), reconciler.backend().take_operations());
+        reconciler.backend_mut().change_property(text, ("value", Str("typed")));
+        reconciler.backend_mut().change_property(checkbox, ("checked", AttributeValue::Bool(true)));
+        reconciler.render(render(Some("a"), Some(false)));
+        assert_eq!(
+            vec![
+                MockOperation::SetProperty(text, "value"),
+                MockOperation::SetProperty(checkbox, "checked"),
+            ],
+            reconciler.backend().take_operations()
+        );
+        assert_eq!(&[("value", Str("a"))], reconciler.backend().properties(text));
+
+        reconciler.render(render(Some("b"), Some(true)));
+        assert_eq!(
+            vec![
+                MockOperation::SetProperty(text, "value"),
+                MockOperation::SetProperty(checkbox, "checked"),
+            ],
+            reconciler.backend().take_operations()
+        );
+
+        // Properties that are no longer rendered are reset
+        reconciler.render(render(None, None));
+        assert_eq!(
+            vec![
+                MockOperation::RemoveProperty(text, "value".to_string()),
+                MockOperation::RemoveProperty(checkbox, "checked".to_string()),
+            ],
+            reconciler.backend().take_operations()
+        );
+
+        // Properties are set after the children of their node, so that a `select` can be set to
+        // the value of one of its `option`s
+        let dom = MockDom::new();
+        let root = dom.root();
+        let mut reconciler = Reconciler::new(dom, root);
+        reconciler.render(select((
+            option((attributes([("value", Str("x"))]), PhantomData::<()>)),
+            option(attributes([("value", Str("y"))])),
+        )).with_properties([("value", Str("y"))]));
+        let operations = reconciler.backend().take_operations();
+        let node = reconciler.backend().children(root)[0];
+        let set_value = operations.iter()
+            .position(|op| *op == MockOperation::SetProperty(node, "value"))
+            .unwrap();
+        assert!(operations[set_value..].iter()
+            .all(|op| !matches!(*op, MockOperation::Insert { parent, .. } if parent == node)));
+
+        // Patches assign properties on every render, as the client's values may have changed
+        assert_eq!(
+            vec![
+                Patch::SetProperty {
+                    node: NodeId(2),
+                    name: "value".to_string(),
+                    value: Str("a"),
+                },
+                Patch::SetProperty {
+                    node: NodeId(3),
+                    name: "checked".to_string(),
+                    value: AttributeValue::Bool(false),
+                },
+            ],
+            diff(render(Some("a"), Some(false)), render(Some("a"), Some(false)))
+        );
+    }
+
+    #[test]
+    fn records_patches() {
+        use super::patch::{diff, NodeId, Patch};
+
+        let render = |items: &[usize]| -> Tag<(), _, _, _> {
+            ol((
+                attributes([("reversed", AttributeValue::Bool(items[0] > items[1]))]),
+                items.iter().map(|&item| li(item.to_string()).with_key(item)).collect::<Vec<_>>(),
+            ))
+        };
+
+        // `ol` is node 1, followed by each `li` and its text
+        let patches = diff(render(&[1, 2, 3]), render(&[3, 2, 1]));
+        assert_eq!(
+            vec![
+                Patch::SetAttribute {
+                    node: NodeId(1),
+                    name: "reversed".to_string(),
+                    value: AttributeValue::Bool(true),
+                },
+                Patch::Move { parent: NodeId(1), from: 2, to: 0 },
+                Patch::Move { parent: NodeId(1), from: 2, to: 1 },
+            ],
+            patches
+        );
+
+        #[cfg(feature = "use_serde")]
+        {
+            extern crate serde_json;
+            assert_eq!(
+                "[{\"SetAttribute\":{\"node\":1,\"name\":\"reversed\",\"value\":true}},\
+                {\"Move\":{\"parent\":1,\"from\":2,\"to\":0}},\
+                {\"Move\":{\"parent\":1,\"from\":2,\"to\":1}}]",
+                serde_json::to_string(&patches).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn serializes_every_patch() {
+        extern crate serde_json;
+        use super::Namespace;
+        use super::patch::{ListenerId, NodeId, Patch};
+
+        let patches = vec![
+            Patch::CreateElement {
+                id: NodeId(1),
+                tag: "svg".to_string(),
+                namespace: Namespace::Svg,
+            },
+            Patch::CreateText { id: NodeId(2), text: "hi".to_string() },
+            Patch::CreateRawHtml { id: NodeId(3), markup: "<b>hi</b>".to_string() },
+            Patch::Insert { parent: NodeId(0), index: 0, child: NodeId(1) },
+            Patch::Move { parent: NodeId(0), from: 1, to: 0 },
+            Patch::Remove { parent: NodeId(0), child: NodeId(2) },
+            Patch::SetAttribute {
+                node: NodeId(1),
+                name: "class".to_string(),
+                value: AttributeValue::OwnedStr("a b".to_string()),
+            },
+            Patch::RemoveAttribute { node: NodeId(1), name: "class".to_string() },
+            Patch::SetText { node: NodeId(2), text: "bye".to_string() },
+            Patch::AttachListener {
+                node: NodeId(1),
+                event_type: "click".to_string(),
+                listener: ListenerId(4),
+            },
+            Patch::DetachListener { node: NodeId(1), listener: ListenerId(4) },
+            Patch::SetProperty {
+                node: NodeId(1),
+                name: "step".to_string(),
+                value: AttributeValue::Float(0.5),
+            },
+            Patch::RemoveProperty { node: NodeId(1), name: "step".to_string() },
+        ];
+        let json = "[\
+            {\"CreateElement\":{\"id\":1,\"tag\":\"svg\",\
+                \"namespace\":\"http://www.w3.org/2000/svg\"}},\
+            {\"CreateText\":{\"id\":2,\"text\":\"hi\"}},\
+            {\"CreateRawHtml\":{\"id\":3,\"markup\":\"<b>hi</b>\"}},\
+            {\"Insert\":{\"parent\":0,\"index\":0,\"child\":1}},\
+            {\"Move\":{\"parent\":0,\"from\":1,\"to\":0}},\
+            {\"Remove\":{\"parent\":0,\"child\":2}},\
+            {\"SetAttribute\":{\"node\":1,\"name\":\"class\",\"value\":\"a b\"}},\
+            {\"RemoveAttribute\":{\"node\":1,\"name\":\"class\"}},\
+            {\"SetText\":{\"node\":2,\"text\":\"bye\"}},\
+            {\"AttachListener\":{\"node\":1,\"event_type\":\"click\",\"listener\":4}},\
+            {\"DetachListener\":{\"node\":1,\"listener\":4}},\
+            {\"SetProperty\":{\"node\":1,\"name\":\"step\",\"value\":0.5}},\
+            {\"RemoveProperty\":{\"node\":1,\"name\":\"step\"}}]";
+
+        assert_eq!(json, serde_json::to_string(&patches).unwrap());
+        assert_eq!(patches, serde_json::from_str::<Vec<Patch>>(json).unwrap());
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn embeds_serialized_state() {
+        use super::document::Document;
+        use super::state::state_script;
+
+        let state = ("</script><!--", "\u{2028}&");
+        let script = state_script::<(), _>(&state).unwrap();
+        assert_eq!(
+            "<script type=\"application/json\" id=\"domafic-state\">\
+            [\"\\u003c/script\\u003e\\u003c!--\",\"\\u2028\\u0026\"]</script>",
+            script.to_string()
+        );
+
+        let document: Document<(), _> = Document::new("text").with_state(&[1, 2]).unwrap();
+        assert_eq!(
+            "<!DOCTYPE html><html><head>\
+            <script type=\"application/json\" id=\"domafic-state\">[1,2]</script></head>\
+            <body>text</body></html>",
+            document.to_string()
+        );
+    }
 }
@@ -1,5 +1,8 @@
 use processors::{Listeners, ListenerProcessor};
 
+use opt_std::marker::PhantomData;
+use opt_std::{mem, ptr};
+
 // TODO make it possible to add fields w/o API breakage-- blocked on pub(restricted)
 /// Description of a `DOM` event that caused a listener to be called.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
@@ -64,3 +67,98 @@ pub fn on<M, F: Fn(Event) -> M>(event_type: &'static str, f: F) -> FnListener<M,
 {
     FnListener { event_type_handled: event_type, f: f }
 }
+
+/// Create a `MapListeners` that converts the messages produced by `listeners` using function `f`.
+///
+/// `f` must not capture any state: it may be a function (such as an enum variant constructor)
+/// or a non-capturing closure. To convert the messages of an entire `DomNode` tree, use
+/// `DomNode::map_messages`.
+///
+/// Passing a function that captures state fails to compile.
+pub fn map_listeners<M, N, L, F>(listeners: L, f: F) -> MapListeners<M, N, L, F>
+    where L: Listeners<M>, F: Fn(M) -> N + Copy
+{
+    check_stateless_mapper(&f);
+    MapListeners::new(listeners)
+}
+
+/// Wrapper for `Listeners` that converts the messages they produce using function `F`.
+#[repr(transparent)]
+pub struct MapListeners<M, N, L, F>(MapperMarker<M, N, F>, L);
+
+impl<M, N, L, F> MapListeners<M, N, L, F> {
+    pub(crate) fn new(listeners: L) -> Self {
+        MapListeners(PhantomData, listeners)
+    }
+
+    pub(crate) fn from_ref(listeners: &L) -> &Self {
+        // Safe because `MapListeners` is a transparent wrapper around `L`
+        unsafe { &*(listeners as *const L as *const Self) }
+    }
+}
+
+impl<M, N, L, F> Listeners<N> for MapListeners<M, N, L, F>
+    where L: Listeners<M>, F: Fn(M) -> N + Copy
+{
+    fn process_all<'a, P: ListenerProcessor<'a, N>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        self.1.process_all::<MapListenerProcessor<M, N, F, P>>(acc)
+    }
+}
+
+/// A single `Listener` whose messages are converted using function `F`.
+#[repr(transparent)]
+pub struct MapListener<M, N, L, F>(MapperMarker<M, N, F>, L);
+
+impl<M, N, L, F> Listener<N> for MapListener<M, N, L, F>
+    where L: Listener<M>, F: Fn(M) -> N + Copy
+{
+    fn event_type_handled(&self) -> &'static str {
+        self.1.event_type_handled()
+    }
+    fn handle_event(&self, event: Event) -> N {
+        stateless_mapper::<F>()(self.1.handle_event(event))
+    }
+}
+
+struct MapListenerProcessor<M, N, F, P>(PhantomData<(MapperMarker<M, N, F>, P)>);
+impl<'a, M, N, F, P> ListenerProcessor<'a, M> for MapListenerProcessor<M, N, F, P>
+    where M: 'a, N: 'a, F: Fn(M) -> N + Copy + 'a, P: ListenerProcessor<'a, N>
+{
+    type Acc = P::Acc;
+    type Error = P::Error;
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn process_mapped<'a, M, N, F, P, L>(acc: &mut P::Acc, listener: &'a L) -> Result<(), P::Error>
+            where M: 'a, N: 'a, L: Listener<M>, F: Fn(M) -> N + Copy + 'a, P: ListenerProcessor<'a, N>
+        {
+            // Safe because `MapListener` is a transparent wrapper around `L`
+            let mapped = unsafe { &*(listener as *const L as *const MapListener<M, N, L, F>) };
+            P::get_processor()(acc, mapped)
+        }
+        process_mapped::<M, N, F, P, L>
+    }
+}
+
+// Mapped nodes and listeners carry their mapping function at the type level rather than storing
+// it, so that they can be viewed in place while the tree is being processed. The public
+// constructors only accept zero-sized functions, which is checked at compile time, and this makes
+// any instance of `F` equivalent to the one the user provided.
+pub(crate) type MapperMarker<M, N, F> = PhantomData<(fn(M) -> N, F)>;
+
+struct StatelessMapper<F>(PhantomData<F>);
+impl<F> StatelessMapper<F> {
+    // Evaluated wherever a mapper is accepted or used, failing the build if `F` captures state
+    const ASSERT_ZERO_SIZED: () = assert!(mem::size_of::<F>() == 0,
+        "Message mapping functions must not capture any state");
+}
+
+pub(crate) fn check_stateless_mapper<F>(_f: &F) {
+    let () = StatelessMapper::<F>::ASSERT_ZERO_SIZED;
+}
+
+fn stateless_mapper<F: Copy>() -> F {
+    let () = StatelessMapper::<F>::ASSERT_ZERO_SIZED;
+    // Safe because `F` is zero-sized, so no memory is read, and it is `Copy`, so this is the same
+    // as copying the function passed to `check_stateless_mapper`
+    unsafe { ptr::NonNull::<F>::dangling().as_ptr().read() }
+}
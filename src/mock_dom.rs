@@ -0,0 +1,454 @@
+use {AttributeValue, DomValue, Event, KeyIter, KeyValue, Listener, Namespace};
+use diff::Backend;
+use vnode::VNode;
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::cell::RefCell;
+use std::fmt;
+
+/// Identifies a node of a `MockDom`.
+///
+/// Nodes are never deallocated, so an id stays valid (and keeps referring to the same node) after
+/// the node is removed from the tree.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MockNodeId(usize);
+
+/// Identifies a listener added to a `MockDom`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MockListenerId(usize);
+
+/// A change made to a `MockDom` through its `Backend` implementation.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum MockOperation {
+    /// A node was created
+    Create(MockNodeId),
+    /// `child` was inserted into `parent` at `index`
+    Insert {
+        /// Parent of the inserted node
+        parent: MockNodeId,
+        /// Position of the inserted node among its siblings
+        index: usize,
+        /// Inserted node
+        child: MockNodeId,
+    },
+    /// A child of `parent` was moved from index `from` to index `to`
+    Move {
+        /// Parent of the moved node
+        parent: MockNodeId,
+        /// Index of the node before it was moved
+        from: usize,
+        /// Index of the node after it was moved
+        to: usize,
+    },
+    /// `child` was removed from `parent`
+    Remove {
+        /// Former parent of the removed node
+        parent: MockNodeId,
+        /// Removed node
+        child: MockNodeId,
+    },
+    /// The text of a text node was replaced
+    SetText(MockNodeId),
+    /// An attribute was set
+    SetAttribute(MockNodeId, String),
+    /// An attribute was removed
+    RemoveAttribute(MockNodeId, String),
+    /// A DOM property was changed
+    SetProperty(MockNodeId, &'static str),
+    /// A DOM property was reset
+    RemoveProperty(MockNodeId, String),
+    /// A listener was added for an event type
+    AddListener(MockNodeId, &'static str),
+    /// A listener was removed
+    RemoveListener(MockNodeId, &'static str),
+    /// An existing node was adopted while hydrating
+    Claim(MockNodeId),
+    /// No existing child of `parent` at `index` could be adopted while hydrating, or the
+    /// attributes of the adopted element differed
+    HydrationMismatch {
+        /// Parent of the missing child
+        parent: MockNodeId,
+        /// Position of the missing child among its siblings
+        index: usize,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum MockValue {
+    Element(String, Namespace),
+    Text(String),
+    RawHtml(String),
+}
+
+struct MockNode<Message: 'static> {
+    value: MockValue,
+    attributes: Vec<(String, AttributeValue)>,
+    properties: Vec<KeyValue>,
+    parent: Option<MockNodeId>,
+    children: Vec<MockNodeId>,
+    listeners: Vec<(MockListenerId, &'static str, *const dyn Listener<Message>, KeyIter)>,
+}
+
+/// An in-memory DOM, used to test rendering without a browser.
+///
+/// `MockDom` implements `diff::Backend`, so it can be rendered into using a `diff::Reconciler`.
+/// Every change made through the `Backend` methods is recorded as a `MockOperation`, and events
+/// can be dispatched to the listeners of a node using `dispatch`.
+///
+/// DOM properties behave like those of a live element: setting a property to the value it
+/// already has isn't recorded, and `change_property` changes a property the way a user would
+/// (for example, by typing into an `input`).
+///
+/// The root node is an element with the tag `root`, and is not part of the `MockDom`'s HTML.
+pub struct MockDom<Message: 'static> {
+    nodes: Vec<MockNode<Message>>,
+    next_listener_id: usize,
+    operations: RefCell<Vec<MockOperation>>,
+}
+
+impl<M: 'static> MockDom<M> {
+    /// Creates an empty `MockDom`.
+    pub fn new() -> MockDom<M> {
+        MockDom {
+            nodes: vec![MockNode {
+                value: MockValue::Element("root".to_string(), Namespace::Html),
+                attributes: Vec::new(),
+                properties: Vec::new(),
+                parent: None,
+                children: Vec::new(),
+                listeners: Vec::new(),
+            }],
+            next_listener_id: 0,
+            operations: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Creates a `MockDom` whose root contains `children`, such as the parsed output of a
+    /// server-side render. No operations are recorded for the initial nodes.
+    pub fn with_children(children: &[VNode]) -> MockDom<M> {
+        let mut dom = MockDom::new();
+        let root = dom.root();
+        for child in children {
+            let child = dom.add_vnode(child, root);
+            dom.nodes[root.0].children.push(child);
+        }
+        dom
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> MockNodeId {
+        MockNodeId(0)
+    }
+
+    /// Returns the value of `node`.
+    pub fn value(&self, node: MockNodeId) -> DomValue<'_> {
+        match self.nodes[node.0].value {
+            MockValue::Element(ref tag, namespace) => DomValue::Element { tag, namespace },
+            MockValue::Text(ref text) => DomValue::Text(text),
+            MockValue::RawHtml(ref markup) => DomValue::RawHtml(markup),
+        }
+    }
+
+    /// Returns the attributes of `node`, in the order they were first set.
+    pub fn attributes(&self, node: MockNodeId) -> &[(String, AttributeValue)] {
+        &self.nodes[node.0].attributes
+    }
+
+    /// Returns the DOM properties of `node`, in the order they were first set.
+    pub fn properties(&self, node: MockNodeId) -> &[KeyValue] {
+        &self.nodes[node.0].properties
+    }
+
+    /// Changes a DOM property of `node` without recording an operation, as if the user had
+    /// changed it. The next render should set it back to the rendered value.
+    pub fn change_property(&mut self, node: MockNodeId, property: KeyValue) {
+        let properties = &mut self.nodes[node.0].properties;
+        properties.retain(|prop| prop.0 != property.0);
+        properties.push(property);
+    }
+
+    /// Returns the children of `node`.
+    pub fn children(&self, node: MockNodeId) -> &[MockNodeId] {
+        &self.nodes[node.0].children
+    }
+
+    /// Returns the parent of `node`, or `None` if it is the root or isn't part of the tree.
+    pub fn parent(&self, node: MockNodeId) -> Option<MockNodeId> {
+        self.nodes[node.0].parent
+    }
+
+    /// Returns the types of the events listened to on `node`.
+    pub fn event_types(&self, node: MockNodeId) -> Vec<&'static str> {
+        self.nodes[node.0].listeners.iter().map(|listener| listener.1).collect()
+    }
+
+    /// Returns the children of the root as `VNode`s.
+    pub fn to_vnodes(&self) -> Vec<VNode> {
+        self.children(self.root()).iter().map(|&child| self.to_vnode(child)).collect()
+    }
+
+    /// Returns the operations recorded since the last call to `take_operations`.
+    pub fn take_operations(&self) -> Vec<MockOperation> {
+        self.operations.borrow_mut().drain(..).collect()
+    }
+
+    /// Sends an event of type `event_type` to `node`, and returns the message produced by its
+    /// listener along with the keys of the node. See `dispatch_event` for details.
+    pub fn dispatch(&self, node: MockNodeId, event_type: &str) -> Option<(M, KeyIter)> {
+        self.dispatch_event(node, Event {
+            type_str: Some(event_type),
+            target_value: None,
+            client_x: 0,
+            client_y: 0,
+            offset_x: 0,
+            offset_y: 0,
+            which_keycode: 0,
+            shift_key: false,
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+        })
+    }
+
+    /// Sends `event` to the first listener on `node` for its `type_str`, and returns the message
+    /// produced along with the keys of the node.
+    ///
+    /// Events don't bubble: only the listeners of `node` itself are considered. Returns `None` if
+    /// no listener handles the event.
+    pub fn dispatch_event(&self, node: MockNodeId, event: Event) -> Option<(M, KeyIter)> {
+        let event_type = event.type_str?;
+        self.nodes[node.0].listeners.iter()
+            .find(|listener| listener.1 == event_type)
+            .map(|&(_, _, listener, ref keys)| {
+                // Safe because `Backend::add_listener` requires listeners to remain valid until
+                // they are removed, either directly or along with their node
+                let message = unsafe { (*listener).handle_event(event) };
+                (message, keys.clone())
+            })
+    }
+
+    fn add_node(&mut self, value: MockValue) -> MockNodeId {
+        let id = MockNodeId(self.nodes.len());
+        self.nodes.push(MockNode {
+            value,
+            attributes: Vec::new(),
+            properties: Vec::new(),
+            parent: None,
+            children: Vec::new(),
+            listeners: Vec::new(),
+        });
+        self.operations.borrow_mut().push(MockOperation::Create(id));
+        id
+    }
+
+    fn add_vnode(&mut self, vnode: &VNode, parent: MockNodeId) -> MockNodeId {
+        let id = MockNodeId(self.nodes.len());
+        let (value, attributes, properties) = match *vnode {
+            VNode::Element { ref tag, namespace, ref attributes, ref properties, .. } => (
+                MockValue::Element(tag.clone(), namespace),
+                attributes.clone(),
+                properties.clone(),
+            ),
+            VNode::Text(ref text) => (MockValue::Text(text.clone()), Vec::new(), Vec::new()),
+            VNode::RawHtml(ref markup) =>
+                (MockValue::RawHtml(markup.clone()), Vec::new(), Vec::new()),
+        };
+        self.nodes.push(MockNode {
+            value,
+            attributes,
+            properties,
+            parent: Some(parent),
+            children: Vec::new(),
+            listeners: Vec::new(),
+        });
+        if let VNode::Element { ref children, .. } = *vnode {
+            for child in children {
+                let child = self.add_vnode(child, id);
+                self.nodes[id.0].children.push(child);
+            }
+        }
+        id
+    }
+
+    fn to_vnode(&self, node: MockNodeId) -> VNode {
+        let node = &self.nodes[node.0];
+        match node.value {
+            MockValue::Element(ref tag, namespace) => VNode::Element {
+                tag: tag.clone(),
+                namespace,
+                key: None,
+                attributes: node.attributes.clone(),
+                properties: node.properties.clone(),
+                children: node.children.iter().map(|&child| self.to_vnode(child)).collect(),
+            },
+            MockValue::Text(ref text) => VNode::Text(text.clone()),
+            MockValue::RawHtml(ref markup) => VNode::RawHtml(markup.clone()),
+        }
+    }
+
+    // Detaches `node` and drops the listeners of it and its descendants
+    fn detach(&mut self, node: MockNodeId) {
+        self.nodes[node.0].parent = None;
+        self.clear_listeners(node);
+    }
+
+    fn clear_listeners(&mut self, node: MockNodeId) {
+        self.nodes[node.0].listeners.clear();
+        for i in 0..self.nodes[node.0].children.len() {
+            let child = self.nodes[node.0].children[i];
+            self.clear_listeners(child);
+        }
+    }
+
+    fn record(&self, operation: MockOperation) {
+        self.operations.borrow_mut().push(operation);
+    }
+}
+
+impl<M: 'static> Default for MockDom<M> {
+    fn default() -> MockDom<M> {
+        MockDom::new()
+    }
+}
+
+impl<M: 'static> fmt::Display for MockDom<M> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for vnode in self.to_vnodes() {
+            vnode.fmt(formatter)?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: 'static> Backend<M> for MockDom<M> {
+    type Node = MockNodeId;
+    type ListenerHandle = MockListenerId;
+
+    fn create_element(&mut self, tag: &str, namespace: Namespace) -> MockNodeId {
+        self.add_node(MockValue::Element(tag.to_string(), namespace))
+    }
+
+    fn create_text(&mut self, text: &str) -> MockNodeId {
+        self.add_node(MockValue::Text(text.to_string()))
+    }
+
+    fn create_raw_html(&mut self, markup: &str) -> MockNodeId {
+        self.add_node(MockValue::RawHtml(markup.to_string()))
+    }
+
+    fn set_text(&mut self, node: &MockNodeId, text: &str) {
+        self.nodes[node.0].value = MockValue::Text(text.to_string());
+        self.record(MockOperation::SetText(*node));
+    }
+
+    fn insert(&mut self, parent: &MockNodeId, index: usize, child: &MockNodeId) {
+        self.nodes[parent.0].children.insert(index, *child);
+        self.nodes[child.0].parent = Some(*parent);
+        self.record(MockOperation::Insert { parent: *parent, index, child: *child });
+    }
+
+    fn move_child(&mut self, parent: &MockNodeId, old_index: usize, new_index: usize) {
+        let child = self.nodes[parent.0].children.remove(old_index);
+        self.nodes[parent.0].children.insert(new_index, child);
+        self.record(MockOperation::Move { parent: *parent, from: old_index, to: new_index });
+    }
+
+    fn remove(&mut self, parent: &MockNodeId, child: &MockNodeId) {
+        self.nodes[parent.0].children.retain(|node| node != child);
+        self.detach(*child);
+        self.record(MockOperation::Remove { parent: *parent, child: *child });
+    }
+
+    fn set_attribute(&mut self, node: &MockNodeId, name: &str, value: &AttributeValue) {
+        {
+            let attributes = &mut self.nodes[node.0].attributes;
+            match attributes.iter().position(|attr| attr.0 == name) {
+                Some(index) => attributes[index].1 = value.clone(),
+                None => attributes.push((name.to_string(), value.clone())),
+            }
+        }
+        self.record(MockOperation::SetAttribute(*node, name.to_string()));
+    }
+
+    fn remove_attribute(&mut self, node: &MockNodeId, name: &str) {
+        self.nodes[node.0].attributes.retain(|attr| attr.0 != name);
+        self.record(MockOperation::RemoveAttribute(*node, name.to_string()));
+    }
+
+    fn set_property(&mut self, node: &MockNodeId, property: &KeyValue) {
+        {
+            let properties = &mut self.nodes[node.0].properties;
+            match properties.iter().position(|prop| prop.0 == property.0) {
+                Some(index) if properties[index] == *property => return,
+                Some(index) => properties[index] = property.clone(),
+                None => properties.push(property.clone()),
+            }
+        }
+        self.record(MockOperation::SetProperty(*node, property.0));
+    }
+
+    fn remove_property(&mut self, node: &MockNodeId, name: &str) {
+        self.nodes[node.0].properties.retain(|prop| prop.0 != name);
+        self.record(MockOperation::RemoveProperty(*node, name.to_string()));
+    }
+
+    unsafe fn add_listener(
+        &mut self,
+        node: &MockNodeId,
+        event_type: &'static str,
+        listener: *const dyn Listener<M>,
+        keys: KeyIter,
+    ) -> MockListenerId {
+        let id = MockListenerId(self.next_listener_id);
+        self.next_listener_id += 1;
+        self.nodes[node.0].listeners.push((id, event_type, listener, keys));
+        self.record(MockOperation::AddListener(*node, event_type));
+        id
+    }
+
+    fn remove_listener(&mut self, node: &MockNodeId, event_type: &'static str, listener: MockListenerId) {
+        self.nodes[node.0].listeners.retain(|x| x.0 != listener);
+        self.record(MockOperation::RemoveListener(*node, event_type));
+    }
+
+    fn claim_child(&mut self, parent: &MockNodeId, index: usize, value: DomValue)
+        -> Option<MockNodeId>
+    {
+        let child = *self.nodes[parent.0].children.get(index)?;
+        if self.value(child) == value {
+            self.record(MockOperation::Claim(child));
+            Some(child)
+        } else {
+            None
+        }
+    }
+
+    fn claimed_attributes_match(&self, node: &MockNodeId, attributes: &[(&str, &AttributeValue)])
+        -> bool
+    {
+        // Properties are written as attributes in server-rendered HTML
+        let node = &self.nodes[node.0];
+        let mut existing = node.attributes.iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .chain(node.properties.iter().map(|&(name, ref value)| (name, value)))
+            .filter(|&(_, value)| *value != AttributeValue::Bool(false))
+            .collect::<Vec<_>>();
+        existing.sort_by_key(|attribute| attribute.0);
+        existing == attributes
+    }
+
+    fn claimed_attribute_names(&self, node: &MockNodeId) -> Vec<String> {
+        self.nodes[node.0].attributes.iter().map(|attribute| attribute.0.clone()).collect()
+    }
+
+    fn remove_unclaimed_children(&mut self, parent: &MockNodeId, index: usize) {
+        while self.nodes[parent.0].children.len() > index {
+            let child = self.nodes[parent.0].children[index];
+            self.remove(parent, &child);
+        }
+    }
+
+    fn hydration_mismatch(&mut self, parent: &MockNodeId, index: usize, _value: DomValue) {
+        self.record(MockOperation::HydrationMismatch { parent: *parent, index });
+    }
+}
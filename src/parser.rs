@@ -0,0 +1,594 @@
+use {AttributeValue, DomNode, DomNodes, DomValue, Namespace};
+use processors::{DomNodeProcessor, EmptyListeners};
+use tags::{is_valid_attribute_name, is_void_element};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::{char, error, fmt};
+
+/// Parses an HTML fragment into a list of owned `HtmlNode`s.
+///
+/// The parser is stricter than a browser: every non-void element must be closed explicitly,
+/// and closing tags must match the element they close. Elements may also be closed by ending
+/// their start tag with `/>`. Any problem is reported as a `ParseError` with its position in
+/// `html`.
+///
+/// Comments are kept as raw HTML, a leading doctype is skipped, and the contents of `script` and
+/// `style` elements are kept exactly as they are written. Common named character references
+/// such as `&amp;` and `&copy;` are decoded, while any others are kept as literal text.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::parser::parse_html;
+/// use domafic::tags::div;
+/// use std::marker::PhantomData;
+///
+/// let fragment = parse_html("<p class=\"intro\">Hello <b>world</b> &amp; friends!</p>").unwrap();
+/// let page = div((
+///     fragment,
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+///
+/// assert_eq!(
+///     "<div><p class=\"intro\">Hello <b>world</b> &amp; friends&#33;</p></div>",
+///     page.to_string()
+/// );
+///
+/// let error = parse_html("<ul>\n  <li>One</ul>").unwrap_err();
+/// assert_eq!((2, 10), (error.line(), error.column()));
+/// ```
+pub fn parse_html(html: &str) -> Result<Vec<HtmlNode>, ParseError> {
+    let mut parser = Parser { html, pos: 0 };
+    parser.skip_whitespace();
+    if parser.starts_with_ignore_case("<!doctype") {
+        parser.skip_past(b'>')?;
+    } else {
+        parser.pos = 0;
+    }
+    parser.parse_nodes(None)
+}
+
+/// An owned node parsed from HTML by `parse_html`.
+///
+/// `HtmlNode`s can be used with any message type, and can be mixed freely with other `DomNode`s.
+#[derive(Debug, Clone)]
+pub struct HtmlNode {
+    value: ParsedValue,
+    attributes: Vec<(String, AttributeValue)>,
+    children: Vec<HtmlNode>,
+}
+
+#[derive(Debug, Clone)]
+enum ParsedValue {
+    Element(String, Namespace),
+    Text(String),
+    RawHtml(String),
+}
+
+impl HtmlNode {
+    /// Returns the tag name and namespace of an element, or the contents of a text node.
+    ///
+    /// Comments and the contents of `script` and `style` elements are `DomValue::RawHtml`.
+    pub fn value(&self) -> DomValue<'_> {
+        match self.value {
+            ParsedValue::Element(ref tag, namespace) => DomValue::Element { tag, namespace },
+            ParsedValue::Text(ref text) => DomValue::Text(text),
+            ParsedValue::RawHtml(ref markup) => DomValue::RawHtml(markup),
+        }
+    }
+
+    /// Returns the attributes of the node, in the order they were written.
+    pub fn attributes(&self) -> &[(String, AttributeValue)] {
+        &self.attributes
+    }
+
+    /// Returns the children of the node.
+    pub fn children(&self) -> &[HtmlNode] {
+        &self.children
+    }
+
+    fn leaf(value: ParsedValue) -> HtmlNode {
+        HtmlNode { value, attributes: Vec::new(), children: Vec::new() }
+    }
+}
+
+impl<M> DomNodes<M> for HtmlNode {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M> DomNode<M> for HtmlNode {
+    type Children = Vec<HtmlNode>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.attributes.get(index).map(|(name, value)| (name.as_str(), value))
+    }
+    fn children(&self) -> &Self::Children {
+        &self.children
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        &EmptyListeners
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (&self.children, &EmptyListeners)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { HtmlNode::value(self) }
+}
+
+/// An error encountered while parsing HTML, along with where it was found.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    line: usize,
+    column: usize,
+}
+
+impl ParseError {
+    /// Returns the kind of error.
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Returns the line on which the error was found, starting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column, in characters, at which the error was found, starting from 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.kind, self.line, self.column)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// The kinds of errors that can be found while parsing HTML.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ParseErrorKind {
+    /// The input ended in the middle of a tag.
+    UnexpectedEof,
+
+    /// A character that isn't allowed at this point of a tag.
+    UnexpectedCharacter(char),
+
+    /// An element that was never closed. The error points at its start tag.
+    UnclosedElement(String),
+
+    /// A comment that was never closed with `-->`.
+    UnclosedComment,
+
+    /// A closing tag found outside of any element.
+    UnexpectedClosingTag(String),
+
+    /// A closing tag that doesn't match the innermost open element.
+    MismatchedClosingTag {
+        /// The tag name of the innermost open element
+        expected: String,
+        /// The tag name of the closing tag
+        found: String,
+    },
+
+    /// A closing tag for a void element, such as `</br>`.
+    ClosingVoidElement(String),
+
+    /// An attribute name that isn't allowed by `tags::is_valid_attribute_name`.
+    InvalidAttributeName(String),
+
+    /// An attribute that appears more than once on the same element.
+    DuplicateAttribute(String),
+
+    /// An attribute followed by `=` without a value.
+    MissingAttributeValue(String),
+
+    /// A numeric character reference to an invalid character, such as `&#xD800;`.
+    InvalidCharacterReference(String),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::UnexpectedEof => write!(f, "unexpected end of input"),
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            ParseErrorKind::UnclosedElement(ref tag) => write!(f, "unclosed element <{}>", tag),
+            ParseErrorKind::UnclosedComment => write!(f, "unclosed comment"),
+            ParseErrorKind::UnexpectedClosingTag(ref tag) =>
+                write!(f, "unexpected closing tag </{}>", tag),
+            ParseErrorKind::MismatchedClosingTag { ref expected, ref found } =>
+                write!(f, "expected closing tag </{}> but found </{}>", expected, found),
+            ParseErrorKind::ClosingVoidElement(ref tag) =>
+                write!(f, "void element <{}> cannot have a closing tag", tag),
+            ParseErrorKind::InvalidAttributeName(ref name) =>
+                write!(f, "invalid attribute name {:?}", name),
+            ParseErrorKind::DuplicateAttribute(ref name) =>
+                write!(f, "duplicate attribute {:?}", name),
+            ParseErrorKind::MissingAttributeValue(ref name) =>
+                write!(f, "missing value for attribute {:?}", name),
+            ParseErrorKind::InvalidCharacterReference(ref reference) =>
+                write!(f, "invalid character reference {}", reference),
+        }
+    }
+}
+
+// The element whose children are being parsed
+struct Parent<'p> {
+    tag: &'p str,
+    namespace: Namespace,
+    start: usize,
+}
+
+struct Parser<'s> {
+    html: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn parse_nodes(&mut self, parent: Option<Parent>) -> Result<Vec<HtmlNode>, ParseError> {
+        let namespace = match parent {
+            // The children of `foreignObject` are HTML again
+            Some(Parent { tag: "foreignObject", namespace: Namespace::Svg, .. }) => Namespace::Html,
+            Some(Parent { namespace, .. }) => namespace,
+            None => Namespace::Html,
+        };
+
+        let mut nodes = Vec::new();
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return match parent {
+                    Some(parent) => Err(self.error(
+                        parent.start,
+                        ParseErrorKind::UnclosedElement(parent.tag.to_string())
+                    )),
+                    None => Ok(nodes),
+                };
+            }
+
+            if rest.starts_with("</") {
+                let start = self.pos;
+                self.pos += 2;
+                let mut tag = self.take_while(is_name_byte).to_string();
+                if tag.is_empty() {
+                    return Err(self.unexpected());
+                }
+                if namespace == Namespace::Html {
+                    tag.make_ascii_lowercase();
+                }
+                self.skip_whitespace();
+                self.expect(b'>')?;
+                return match parent {
+                    Some(ref parent) if parent.tag.eq_ignore_ascii_case(&tag) => Ok(nodes),
+                    _ if namespace == Namespace::Html && is_void_element(&tag) =>
+                        Err(self.error(start, ParseErrorKind::ClosingVoidElement(tag))),
+                    Some(parent) => Err(self.error(start, ParseErrorKind::MismatchedClosingTag {
+                        expected: parent.tag.to_string(),
+                        found: tag,
+                    })),
+                    None => Err(self.error(start, ParseErrorKind::UnexpectedClosingTag(tag))),
+                };
+            } else if let Some(comment) = rest.strip_prefix("<!--") {
+                let end = match comment.find("-->") {
+                    Some(end) => end + 7,
+                    None => return Err(self.error(self.pos, ParseErrorKind::UnclosedComment)),
+                };
+                nodes.push(HtmlNode::leaf(ParsedValue::RawHtml(rest[..end].to_string())));
+                self.pos += end;
+            } else if rest.starts_with("<!") {
+                self.pos += 1;
+                return Err(self.unexpected());
+            } else if is_tag_start(rest) {
+                nodes.push(self.parse_element(namespace)?);
+            } else {
+                // Text runs until the next tag, so a `<` that doesn't start a tag is kept as text
+                let start = self.pos;
+                let len = rest.match_indices('<')
+                    .map(|(index, _)| index)
+                    .find(|&index| is_markup_start(&rest[index..]))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let text = self.decode(&rest[..len], start)?;
+                nodes.push(HtmlNode::leaf(ParsedValue::Text(text)));
+            }
+        }
+    }
+
+    fn parse_element(&mut self, parent_namespace: Namespace) -> Result<HtmlNode, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut tag = self.take_while(is_name_byte).to_string();
+        let namespace = if tag.eq_ignore_ascii_case("svg") {
+            Namespace::Svg
+        } else if tag.eq_ignore_ascii_case("math") {
+            Namespace::MathMl
+        } else {
+            parent_namespace
+        };
+        // SVG tag names such as `foreignObject` are case-sensitive
+        if namespace != Namespace::Svg || tag.eq_ignore_ascii_case("svg") {
+            tag.make_ascii_lowercase();
+        }
+        match self.peek() {
+            Some(b'>') | Some(b'/') => {},
+            Some(byte) if is_whitespace(byte) => {},
+            None => return Err(self.error(self.pos, ParseErrorKind::UnexpectedEof)),
+            Some(_) => return Err(self.unexpected()),
+        }
+
+        let mut attributes: Vec<(String, AttributeValue)> = Vec::new();
+        let self_closing = loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Err(self.error(self.pos, ParseErrorKind::UnexpectedEof)),
+                Some(b'>') => {
+                    self.pos += 1;
+                    break false;
+                }
+                Some(b'/') if self.rest().starts_with("/>") => {
+                    self.pos += 2;
+                    break true;
+                }
+                _ => {},
+            }
+
+            let name_start = self.pos;
+            let mut name = self.take_while(|byte|
+                !is_whitespace(byte) && !matches!(byte, b'=' | b'>' | b'/')
+            ).to_string();
+            if name.is_empty() {
+                return Err(self.unexpected());
+            }
+            if namespace == Namespace::Html {
+                name.make_ascii_lowercase();
+            }
+            if !is_valid_attribute_name(&name) {
+                return Err(self.error(name_start, ParseErrorKind::InvalidAttributeName(name)));
+            }
+            if attributes.iter().any(|attr| attr.0 == name) {
+                return Err(self.error(name_start, ParseErrorKind::DuplicateAttribute(name)));
+            }
+
+            self.skip_whitespace();
+            let value = if self.peek() == Some(b'=') {
+                self.pos += 1;
+                self.skip_whitespace();
+                let (raw, raw_start) = match self.peek() {
+                    Some(quote) if quote == b'"' || quote == b'\'' => {
+                        self.pos += 1;
+                        let raw_start = self.pos;
+                        let raw = self.take_while(|byte| byte != quote);
+                        self.expect(quote)?;
+                        (raw, raw_start)
+                    }
+                    _ => {
+                        let raw_start = self.pos;
+                        let raw = self.take_while(|byte| !is_whitespace(byte) && byte != b'>');
+                        if raw.is_empty() {
+                            return Err(
+                                self.error(raw_start, ParseErrorKind::MissingAttributeValue(name))
+                            );
+                        }
+                        (raw, raw_start)
+                    }
+                };
+                AttributeValue::OwnedStr(self.decode(raw, raw_start)?)
+            } else {
+                AttributeValue::Bool(true)
+            };
+            attributes.push((name, value));
+        };
+
+        let children = if self_closing || (namespace == Namespace::Html && is_void_element(&tag)) {
+            Vec::new()
+        } else if namespace == Namespace::Html && matches!(tag.as_str(), "script" | "style") {
+            let contents = self.parse_raw_text(&tag, start)?;
+            if contents.is_empty() {
+                Vec::new()
+            } else {
+                vec![HtmlNode::leaf(ParsedValue::RawHtml(contents.to_string()))]
+            }
+        } else if namespace == Namespace::Html && matches!(tag.as_str(), "textarea" | "title") {
+            let contents_start = self.pos;
+            let contents = self.parse_raw_text(&tag, start)?;
+            if contents.is_empty() {
+                Vec::new()
+            } else {
+                vec![HtmlNode::leaf(ParsedValue::Text(self.decode(contents, contents_start)?))]
+            }
+        } else {
+            self.parse_nodes(Some(Parent { tag: &tag, namespace, start }))?
+        };
+
+        Ok(HtmlNode { value: ParsedValue::Element(tag, namespace), attributes, children })
+    }
+
+    // Reads the contents of an element that can't contain other elements, up to and including
+    // its closing tag
+    fn parse_raw_text(&mut self, tag: &str, start: usize) -> Result<&'s str, ParseError> {
+        let rest = self.rest();
+        let mut search_from = 0;
+        while let Some(index) = rest[search_from..].find("</") {
+            let end = search_from + index;
+            let after = &rest[end + 2..];
+            if after.len() >= tag.len() &&
+                after.as_bytes()[..tag.len()].eq_ignore_ascii_case(tag.as_bytes()) &&
+                after.as_bytes().get(tag.len()).is_none_or(|&byte|
+                    byte == b'>' || byte == b'/' || is_whitespace(byte))
+            {
+                self.pos += end + 2 + tag.len();
+                self.skip_whitespace();
+                self.expect(b'>')?;
+                return Ok(&rest[..end]);
+            }
+            search_from = end + 2;
+        }
+        Err(self.error(start, ParseErrorKind::UnclosedElement(tag.to_string())))
+    }
+
+    // Replaces character references in `raw`, which starts at `offset` in the input
+    fn decode(&self, raw: &str, offset: usize) -> Result<String, ParseError> {
+        let mut decoded = String::with_capacity(raw.len());
+        let mut last = 0;
+        for (index, _) in raw.match_indices('&') {
+            if index < last {
+                continue;
+            }
+            let after = &raw[index + 1..];
+            let len = after.bytes().take_while(|&byte| byte.is_ascii_alphanumeric() || byte == b'#')
+                .count();
+            // An `&` that isn't followed by a reference is just an ampersand
+            if len == 0 || !after[len..].starts_with(';') {
+                continue;
+            }
+
+            let name = &after[..len];
+            let number = if name.starts_with("#x") || name.starts_with("#X") {
+                Some(u32::from_str_radix(&name[2..], 16).ok())
+            } else {
+                name.strip_prefix('#').map(|digits| digits.parse::<u32>().ok())
+            };
+            let c = match number {
+                Some(code) => match code.and_then(char::from_u32).filter(|&c| c != '\0') {
+                    Some(c) => c,
+                    None => return Err(self.error(
+                        offset + index,
+                        ParseErrorKind::InvalidCharacterReference(format!("&{};", name))
+                    )),
+                },
+                None => match named_reference(name) {
+                    Some(c) => c,
+                    // Unknown names are kept as they are written, as browsers do
+                    None => continue,
+                },
+            };
+            decoded.push_str(&raw[last..index]);
+            decoded.push(c);
+            last = index + len + 2;
+        }
+        decoded.push_str(&raw[last..]);
+        Ok(decoded)
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.html[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.html.as_bytes().get(self.pos).cloned()
+    }
+
+    fn starts_with_ignore_case(&self, prefix: &str) -> bool {
+        let rest = self.rest().as_bytes();
+        rest.len() >= prefix.len() && rest[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+    }
+
+    // Advances past ASCII bytes matching `predicate`, returning them
+    fn take_while<F: Fn(u8) -> bool>(&mut self, predicate: F) -> &'s str {
+        let rest = self.rest();
+        let len = rest.bytes().take_while(|&byte| predicate(byte)).count();
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(is_whitespace);
+    }
+
+    fn skip_past(&mut self, byte: u8) -> Result<(), ParseError> {
+        self.take_while(|b| b != byte);
+        self.expect(byte)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(b) if b == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.unexpected()),
+            None => Err(self.error(self.pos, ParseErrorKind::UnexpectedEof)),
+        }
+    }
+
+    // Reports the character at the current position as unexpected
+    fn unexpected(&self) -> ParseError {
+        match self.rest().chars().next() {
+            Some(c) => self.error(self.pos, ParseErrorKind::UnexpectedCharacter(c)),
+            None => self.error(self.pos, ParseErrorKind::UnexpectedEof),
+        }
+    }
+
+    fn error(&self, offset: usize, kind: ParseErrorKind) -> ParseError {
+        let before = &self.html[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        ParseError {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0C')
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b':' | b'.')
+}
+
+fn is_tag_start(text: &str) -> bool {
+    text.len() > 1 && text.as_bytes()[0] == b'<' && text.as_bytes()[1].is_ascii_alphabetic()
+}
+
+// Whether `text` starts with a tag, closing tag, comment or other markup declaration
+fn is_markup_start(text: &str) -> bool {
+    is_tag_start(text) || text.starts_with("</") || text.starts_with("<!")
+}
+
+fn named_reference(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '\u{A9}',
+        "reg" => '\u{AE}',
+        "trade" => '\u{2122}',
+        "hellip" => '\u{2026}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201C}',
+        "rdquo" => '\u{201D}',
+        "laquo" => '\u{AB}',
+        "raquo" => '\u{BB}',
+        "middot" => '\u{B7}',
+        "bull" => '\u{2022}',
+        "times" => '\u{D7}',
+        "divide" => '\u{F7}',
+        "deg" => '\u{B0}',
+        "euro" => '\u{20AC}',
+        "pound" => '\u{A3}',
+        "yen" => '\u{A5}',
+        "cent" => '\u{A2}',
+        "sect" => '\u{A7}',
+        "para" => '\u{B6}',
+        "shy" => '\u{AD}',
+        _ => return None,
+    })
+}
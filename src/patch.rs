@@ -0,0 +1,780 @@
+use {AttributeValue, DomNode, Event, KeyIter, KeyValue, Listener, Namespace};
+use diff::{Backend, Reconciler};
+#[cfg(feature = "use_serde")]
+extern crate serde;
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Identifies a node created by a `PatchRecorder`. The root is always `NodeId(0)`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct NodeId(pub u32);
+
+/// Identifies a listener attached by a `PatchRecorder`.
+///
+/// Clients applying patches should report events to the listener with this id, which can then be
+/// handled using `PatchRecorder::dispatch`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ListenerId(pub u32);
+
+/// A single change to a DOM.
+///
+/// Patches are applied in order. Node ids are assigned by `Create*` patches and are never reused.
+/// With the `use_serde` feature, patches can be serialized (for example, to JSON) and sent to a
+/// client that applies them to its DOM, and deserialized again from self-describing formats.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Patch {
+    /// Create an element that is not yet attached to the DOM
+    CreateElement {
+        /// Id of the new element
+        id: NodeId,
+        /// Tag name, such as `div`
+        tag: String,
+        /// Namespace of the element
+        namespace: Namespace,
+    },
+    /// Create a text node that is not yet attached to the DOM
+    CreateText {
+        /// Id of the new node
+        id: NodeId,
+        /// Text of the node
+        text: String,
+    },
+    /// Create a node from trusted HTML markup that is not yet attached to the DOM
+    CreateRawHtml {
+        /// Id of the new node
+        id: NodeId,
+        /// HTML markup
+        markup: String,
+    },
+    /// Insert `child` as the child of `parent` at `index`
+    Insert {
+        /// Node to insert into
+        parent: NodeId,
+        /// Position among the children of `parent`
+        index: usize,
+        /// Node to insert
+        child: NodeId,
+    },
+    /// Move the child of `parent` at index `from` to index `to`
+    Move {
+        /// Parent of the moved node
+        parent: NodeId,
+        /// Index of the node before it is moved
+        from: usize,
+        /// Index of the node after it is moved
+        to: usize,
+    },
+    /// Remove `child` and its descendants from `parent`. Their ids are never used again.
+    Remove {
+        /// Parent of the removed node
+        parent: NodeId,
+        /// Node to remove
+        child: NodeId,
+    },
+    /// Set an attribute, replacing any previous value
+    ///
+    /// `Bool(true)` values set the attribute with an empty value, as when writing HTML.
+    /// `Bool(false)` attributes are removed with `RemoveAttribute` instead.
+    SetAttribute {
+        /// Node to update
+        node: NodeId,
+        /// Attribute name
+        name: String,
+        /// Attribute value
+        value: AttributeValue,
+    },
+    /// Remove an attribute
+    RemoveAttribute {
+        /// Node to update
+        node: NodeId,
+        /// Attribute name
+        name: String,
+    },
+    /// Replace the text of a text node
+    SetText {
+        /// Node to update
+        node: NodeId,
+        /// New text
+        text: String,
+    },
+    /// Start reporting events of type `event_type` on `node` to the listener `listener`
+    AttachListener {
+        /// Node to listen to
+        node: NodeId,
+        /// Type of event, such as `click`
+        event_type: String,
+        /// Id to report events to
+        listener: ListenerId,
+    },
+    /// Stop reporting events to `listener`
+    DetachListener {
+        /// Node the listener was attached to
+        node: NodeId,
+        /// Id of the listener
+        listener: ListenerId,
+    },
+    /// Assign a DOM property, such as the `value` of an `input`, if its current value differs
+    ///
+    /// Properties are set on every render, as the user may have changed them since the last one.
+    SetProperty {
+        /// Node to update
+        node: NodeId,
+        /// Property name
+        name: String,
+        /// Property value
+        value: AttributeValue,
+    },
+    /// Reset a DOM property that is no longer rendered
+    RemoveProperty {
+        /// Node to update
+        node: NodeId,
+        /// Property name
+        name: String,
+    },
+}
+
+#[derive(Default)]
+struct PatchNode {
+    children: Vec<NodeId>,
+    listeners: Vec<ListenerId>,
+}
+
+/// A `diff::Backend` that records the changes made to it as `Patch`es rather than applying them.
+///
+/// Use it with a `diff::Reconciler` to compute the patches that bring a DOM from one render to
+/// the next. Listeners are assigned `ListenerId`s, so events reported by whoever applies the
+/// patches can be handled with `dispatch`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::diff::Reconciler;
+/// use domafic::patch::{ListenerId, NodeId, Patch, PatchRecorder};
+/// use domafic::on;
+/// use domafic::tags::button;
+///
+/// let render = |count: i32| button((on("click", |_| "clicked"), count.to_string()));
+///
+/// let recorder = PatchRecorder::new();
+/// let root = recorder.root();
+/// let mut reconciler = Reconciler::new(recorder, root);
+///
+/// reconciler.render(render(0));
+/// assert_eq!(5, reconciler.backend().take_patches().len());
+///
+/// // The listener hasn't changed, so it stays attached, and the text is updated in place
+/// reconciler.render(render(1));
+/// assert_eq!(
+///     vec![Patch::SetText { node: NodeId(2), text: "1".to_string() }],
+///     reconciler.backend().take_patches()
+/// );
+///
+/// let (message, _keys) = reconciler.backend().dispatch(ListenerId(0), "click").unwrap();
+/// assert_eq!("clicked", message);
+/// ```
+pub struct PatchRecorder<Message: 'static> {
+    next_node_id: u32,
+    next_listener_id: u32,
+    nodes: HashMap<NodeId, PatchNode>,
+    listeners: HashMap<ListenerId, (*const dyn Listener<Message>, KeyIter)>,
+    patches: RefCell<Vec<Patch>>,
+}
+
+impl<M: 'static> PatchRecorder<M> {
+    /// Creates a `PatchRecorder` with an empty root.
+    pub fn new() -> PatchRecorder<M> {
+        let mut nodes = HashMap::new();
+        nodes.insert(NodeId(0), PatchNode::default());
+        PatchRecorder {
+            next_node_id: 1,
+            next_listener_id: 0,
+            nodes,
+            listeners: HashMap::new(),
+            patches: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Returns the patches recorded since the last call to `take_patches`.
+    pub fn take_patches(&self) -> Vec<Patch> {
+        self.patches.borrow_mut().drain(..).collect()
+    }
+
+    /// Sends an event of type `event_type` to `listener`, and returns the message produced along
+    /// with the keys of the listener's node. See `dispatch_event` for details.
+    pub fn dispatch(&self, listener: ListenerId, event_type: &str) -> Option<(M, KeyIter)> {
+        self.dispatch_event(listener, Event {
+            type_str: Some(event_type),
+            target_value: None,
+            client_x: 0,
+            client_y: 0,
+            offset_x: 0,
+            offset_y: 0,
+            which_keycode: 0,
+            shift_key: false,
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+        })
+    }
+
+    /// Sends `event` to `listener`, and returns the message produced along with the keys of the
+    /// listener's node.
+    ///
+    /// Returns `None` if the listener has been detached, as events may arrive from clients after
+    /// the patches that detach their listener were sent.
+    pub fn dispatch_event(&self, listener: ListenerId, event: Event) -> Option<(M, KeyIter)> {
+        self.listeners.get(&listener).map(|&(listener, ref keys)| {
+            // Safe because `Backend::add_listener` requires listeners to remain valid until they
+            // are removed, either directly or along with their node
+            let message = unsafe { (*listener).handle_event(event) };
+            (message, keys.clone())
+        })
+    }
+
+    fn add_node(&mut self) -> NodeId {
+        let id = NodeId(self.next_node_id);
+        self.next_node_id += 1;
+        self.nodes.insert(id, PatchNode::default());
+        id
+    }
+
+    // Forgets `node` and its descendants, along with their listeners
+    fn forget(&mut self, node: NodeId) {
+        if let Some(node) = self.nodes.remove(&node) {
+            for listener in node.listeners {
+                self.listeners.remove(&listener);
+            }
+            for child in node.children {
+                self.forget(child);
+            }
+        }
+    }
+
+    fn record(&self, patch: Patch) {
+        self.patches.borrow_mut().push(patch);
+    }
+}
+
+impl<M: 'static> Default for PatchRecorder<M> {
+    fn default() -> PatchRecorder<M> {
+        PatchRecorder::new()
+    }
+}
+
+impl<M: 'static> Backend<M> for PatchRecorder<M> {
+    type Node = NodeId;
+    type ListenerHandle = ListenerId;
+
+    fn create_element(&mut self, tag: &str, namespace: Namespace) -> NodeId {
+        let id = self.add_node();
+        self.record(Patch::CreateElement { id, tag: tag.to_string(), namespace });
+        id
+    }
+
+    fn create_text(&mut self, text: &str) -> NodeId {
+        let id = self.add_node();
+        self.record(Patch::CreateText { id, text: text.to_string() });
+        id
+    }
+
+    fn create_raw_html(&mut self, markup: &str) -> NodeId {
+        let id = self.add_node();
+        self.record(Patch::CreateRawHtml { id, markup: markup.to_string() });
+        id
+    }
+
+    fn set_text(&mut self, node: &NodeId, text: &str) {
+        self.record(Patch::SetText { node: *node, text: text.to_string() });
+    }
+
+    fn insert(&mut self, parent: &NodeId, index: usize, child: &NodeId) {
+        if let Some(parent) = self.nodes.get_mut(parent) {
+            parent.children.insert(index, *child);
+        }
+        self.record(Patch::Insert { parent: *parent, index, child: *child });
+    }
+
+    fn move_child(&mut self, parent: &NodeId, old_index: usize, new_index: usize) {
+        if let Some(parent) = self.nodes.get_mut(parent) {
+            let child = parent.children.remove(old_index);
+            parent.children.insert(new_index, child);
+        }
+        self.record(Patch::Move { parent: *parent, from: old_index, to: new_index });
+    }
+
+    fn remove(&mut self, parent: &NodeId, child: &NodeId) {
+        if let Some(parent) = self.nodes.get_mut(parent) {
+            parent.children.retain(|node| node != child);
+        }
+        self.forget(*child);
+        self.record(Patch::Remove { parent: *parent, child: *child });
+    }
+
+    fn set_attribute(&mut self, node: &NodeId, name: &str, value: &AttributeValue) {
+        if let AttributeValue::Bool(false) = *value {
+            return self.remove_attribute(node, name);
+        }
+        self.record(Patch::SetAttribute {
+            node: *node,
+            name: name.to_string(),
+            value: value.clone(),
+        });
+    }
+
+    fn remove_attribute(&mut self, node: &NodeId, name: &str) {
+        self.record(Patch::RemoveAttribute { node: *node, name: name.to_string() });
+    }
+
+    fn set_property(&mut self, node: &NodeId, property: &KeyValue) {
+        self.record(Patch::SetProperty {
+            node: *node,
+            name: property.0.to_string(),
+            value: property.1.clone(),
+        });
+    }
+
+    fn remove_property(&mut self, node: &NodeId, name: &str) {
+        self.record(Patch::RemoveProperty { node: *node, name: name.to_string() });
+    }
+
+    unsafe fn add_listener(
+        &mut self,
+        node: &NodeId,
+        event_type: &'static str,
+        listener: *const dyn Listener<M>,
+        keys: KeyIter,
+    ) -> ListenerId {
+        let id = ListenerId(self.next_listener_id);
+        self.next_listener_id += 1;
+        self.listeners.insert(id, (listener, keys));
+        if let Some(node) = self.nodes.get_mut(node) {
+            node.listeners.push(id);
+        }
+        self.record(Patch::AttachListener {
+            node: *node,
+            event_type: event_type.to_string(),
+            listener: id,
+        });
+        id
+    }
+
+    fn remove_listener(&mut self, node: &NodeId, _event_type: &'static str, listener: ListenerId) {
+        self.listeners.remove(&listener);
+        if let Some(node) = self.nodes.get_mut(node) {
+            node.listeners.retain(|&id| id != listener);
+        }
+        self.record(Patch::DetachListener { node: *node, listener });
+    }
+}
+
+/// Computes the patches that turn the DOM for `old` into the DOM for `new`.
+///
+/// The patches for the initial render of `old` are discarded, so node ids start after those of
+/// `old`'s nodes. Since the listeners of `new` are dropped when this function returns, the
+/// `ListenerId`s in the patches can't be dispatched to. To handle events, use a `PatchRecorder`
+/// with a `Reconciler` directly.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::patch::{diff, NodeId, Patch};
+/// use domafic::tags::{attributes, div};
+/// use domafic::AttributeValue::Str;
+/// use std::marker::PhantomData;
+///
+/// let render = |class: &'static str| div((attributes([("class", Str(class))]), PhantomData::<()>));
+/// assert_eq!(
+///     vec![Patch::SetAttribute {
+///         node: NodeId(1), name: "class".to_string(), value: Str("active"),
+///     }],
+///     diff(render("inactive"), render("active"))
+/// );
+/// ```
+pub fn diff<M: 'static, D: DomNode<M>>(old: D, new: D) -> Vec<Patch> {
+    let recorder = PatchRecorder::new();
+    let root = recorder.root();
+    let mut reconciler = Reconciler::new(recorder, root);
+    reconciler.render(old);
+    reconciler.backend().take_patches();
+    reconciler.render(new);
+    reconciler.backend().take_patches()
+}
+
+/// Patches are serialized like a derived `Serialize` implementation would: each patch is a map
+/// from its variant name to its fields. Node and listener ids are serialized as numbers,
+/// namespaces as their URIs, and attribute values as strings, except for `Bool`, `Int` and
+/// `Float`.
+#[cfg(feature = "use_serde")]
+impl self::serde::Serialize for Patch {
+    fn serialize<S: self::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use self::serde::ser::SerializeStructVariant;
+
+        macro_rules! variant {
+            ($index:expr, $name:expr, $($field:ident),*) => {{
+                let mut variant = serializer.serialize_struct_variant(
+                    "Patch", $index, $name, [$(stringify!($field)),*].len())?;
+                $(variant.serialize_field(stringify!($field), $field)?;)*
+                variant.end()
+            }}
+        }
+
+        match *self {
+            Patch::CreateElement { ref id, ref tag, ref namespace } =>
+                variant!(0, "CreateElement", id, tag, namespace),
+            Patch::CreateText { ref id, ref text } =>
+                variant!(1, "CreateText", id, text),
+            Patch::CreateRawHtml { ref id, ref markup } =>
+                variant!(2, "CreateRawHtml", id, markup),
+            Patch::Insert { ref parent, ref index, ref child } =>
+                variant!(3, "Insert", parent, index, child),
+            Patch::Move { ref parent, ref from, ref to } =>
+                variant!(4, "Move", parent, from, to),
+            Patch::Remove { ref parent, ref child } =>
+                variant!(5, "Remove", parent, child),
+            Patch::SetAttribute { ref node, ref name, ref value } =>
+                variant!(6, "SetAttribute", node, name, value),
+            Patch::RemoveAttribute { ref node, ref name } =>
+                variant!(7, "RemoveAttribute", node, name),
+            Patch::SetText { ref node, ref text } =>
+                variant!(8, "SetText", node, text),
+            Patch::AttachListener { ref node, ref event_type, ref listener } =>
+                variant!(9, "AttachListener", node, event_type, listener),
+            Patch::DetachListener { ref node, ref listener } =>
+                variant!(10, "DetachListener", node, listener),
+            Patch::SetProperty { ref node, ref name, ref value } =>
+                variant!(11, "SetProperty", node, name, value),
+            Patch::RemoveProperty { ref node, ref name } =>
+                variant!(12, "RemoveProperty", node, name),
+        }
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl self::serde::Serialize for NodeId {
+    fn serialize<S: self::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl self::serde::Serialize for ListenerId {
+    fn serialize<S: self::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl self::serde::Serialize for Namespace {
+    fn serialize<S: self::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.uri())
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl self::serde::Serialize for AttributeValue {
+    fn serialize<S: self::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            AttributeValue::Bool(value) => serializer.serialize_bool(value),
+            AttributeValue::Int(value) => serializer.serialize_i64(value),
+            AttributeValue::Float(value) => serializer.serialize_f64(value),
+            ref value => serializer.collect_str(value),
+        }
+    }
+}
+
+#[cfg(feature = "use_serde")]
+const PATCH_VARIANTS: &[&str] = &[
+    "CreateElement", "CreateText", "CreateRawHtml", "Insert", "Move", "Remove", "SetAttribute",
+    "RemoveAttribute", "SetText", "AttachListener", "DetachListener", "SetProperty",
+    "RemoveProperty",
+];
+
+// Field names of each variant, indexed like `PATCH_VARIANTS`
+#[cfg(feature = "use_serde")]
+const PATCH_FIELDS: &[&[&str]] = &[
+    &["id", "tag", "namespace"],
+    &["id", "text"],
+    &["id", "markup"],
+    &["parent", "index", "child"],
+    &["parent", "from", "to"],
+    &["parent", "child"],
+    &["node", "name", "value"],
+    &["node", "name"],
+    &["node", "text"],
+    &["node", "event_type", "listener"],
+    &["node", "listener"],
+    &["node", "name", "value"],
+    &["node", "name"],
+];
+
+/// Patches are deserialized from the format they are serialized to.
+///
+/// Attribute values are not tagged with their kind, so they can only be deserialized from
+/// self-describing formats such as JSON. Strings and token lists are both deserialized as
+/// `OwnedStr`.
+#[cfg(feature = "use_serde")]
+impl<'de> self::serde::Deserialize<'de> for Patch {
+    fn deserialize<D: self::serde::Deserializer<'de>>(deserializer: D) -> Result<Patch, D::Error> {
+        use self::serde::de::{self, EnumAccess, VariantAccess};
+        use std::fmt;
+
+        struct Variant(usize);
+
+        impl<'de> de::Deserialize<'de> for Variant {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Variant, D::Error> {
+                struct VariantVisitor;
+
+                impl<'de> de::Visitor<'de> for VariantVisitor {
+                    type Value = Variant;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("a patch variant")
+                    }
+
+                    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Variant, E> {
+                        if (index as usize) < PATCH_VARIANTS.len() {
+                            Ok(Variant(index as usize))
+                        } else {
+                            Err(E::invalid_value(de::Unexpected::Unsigned(index), &self))
+                        }
+                    }
+
+                    fn visit_str<E: de::Error>(self, name: &str) -> Result<Variant, E> {
+                        PATCH_VARIANTS.iter().position(|variant| *variant == name)
+                            .map(Variant)
+                            .ok_or_else(|| E::unknown_variant(name, PATCH_VARIANTS))
+                    }
+                }
+
+                deserializer.deserialize_identifier(VariantVisitor)
+            }
+        }
+
+        // Fields of every variant, collected before the variant is built
+        #[derive(Default)]
+        struct Fields {
+            id: Option<NodeId>,
+            tag: Option<String>,
+            namespace: Option<Namespace>,
+            text: Option<String>,
+            markup: Option<String>,
+            parent: Option<NodeId>,
+            index: Option<usize>,
+            child: Option<NodeId>,
+            from: Option<usize>,
+            to: Option<usize>,
+            node: Option<NodeId>,
+            name: Option<String>,
+            value: Option<AttributeValue>,
+            event_type: Option<String>,
+            listener: Option<ListenerId>,
+        }
+
+        // Reads the value of a field from either a map or a sequence
+        trait FieldSource<'de> {
+            type Error: de::Error;
+            fn next<T: de::Deserialize<'de>>(&mut self) -> Result<Option<T>, Self::Error>;
+        }
+
+        struct MapSource<A>(A);
+
+        impl<'de, A: de::MapAccess<'de>> FieldSource<'de> for MapSource<A> {
+            type Error = A::Error;
+            fn next<T: de::Deserialize<'de>>(&mut self) -> Result<Option<T>, A::Error> {
+                self.0.next_value().map(Some)
+            }
+        }
+
+        struct SeqSource<A>(A);
+
+        impl<'de, A: de::SeqAccess<'de>> FieldSource<'de> for SeqSource<A> {
+            type Error = A::Error;
+            fn next<T: de::Deserialize<'de>>(&mut self) -> Result<Option<T>, A::Error> {
+                self.0.next_element()
+            }
+        }
+
+        impl Fields {
+            fn read<'de, S: FieldSource<'de>>(&mut self, name: &str, source: &mut S)
+                -> Result<bool, S::Error>
+            {
+                macro_rules! read {
+                    ($($field:ident),*) => {
+                        match name {
+                            $(stringify!($field) => {
+                                if self.$field.is_some() {
+                                    return Err(de::Error::duplicate_field(stringify!($field)));
+                                }
+                                self.$field = source.next()?;
+                                Ok(self.$field.is_some())
+                            })*
+                            _ => source.next::<de::IgnoredAny>().map(|value| value.is_some()),
+                        }
+                    }
+                }
+                read!(id, tag, namespace, text, markup, parent, index, child, from, to, node,
+                      name, value, event_type, listener)
+            }
+
+            fn build<E: de::Error>(self, variant: usize) -> Result<Patch, E> {
+                macro_rules! build {
+                    ($variant:ident, $($field:ident),*) => {
+                        Patch::$variant {
+                            $($field: self.$field.ok_or_else(
+                                || E::missing_field(stringify!($field)))?),*
+                        }
+                    }
+                }
+                Ok(match variant {
+                    0 => build!(CreateElement, id, tag, namespace),
+                    1 => build!(CreateText, id, text),
+                    2 => build!(CreateRawHtml, id, markup),
+                    3 => build!(Insert, parent, index, child),
+                    4 => build!(Move, parent, from, to),
+                    5 => build!(Remove, parent, child),
+                    6 => build!(SetAttribute, node, name, value),
+                    7 => build!(RemoveAttribute, node, name),
+                    8 => build!(SetText, node, text),
+                    9 => build!(AttachListener, node, event_type, listener),
+                    10 => build!(DetachListener, node, listener),
+                    11 => build!(SetProperty, node, name, value),
+                    _ => build!(RemoveProperty, node, name),
+                })
+            }
+        }
+
+        struct FieldsVisitor(usize);
+
+        impl<'de> de::Visitor<'de> for FieldsVisitor {
+            type Value = Patch;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "the fields of a {} patch", PATCH_VARIANTS[self.0])
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Patch, A::Error> {
+                let mut fields = Fields::default();
+                let mut source = MapSource(map);
+                while let Some(name) = source.0.next_key::<String>()? {
+                    fields.read(&name, &mut source)?;
+                }
+                fields.build(self.0)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Patch, A::Error> {
+                let mut fields = Fields::default();
+                let mut source = SeqSource(seq);
+                for (index, name) in PATCH_FIELDS[self.0].iter().enumerate() {
+                    if !fields.read(name, &mut source)? {
+                        return Err(de::Error::invalid_length(index, &self));
+                    }
+                }
+                fields.build(self.0)
+            }
+        }
+
+        struct PatchVisitor;
+
+        impl<'de> de::Visitor<'de> for PatchVisitor {
+            type Value = Patch;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a patch")
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Patch, A::Error> {
+                let (Variant(index), variant) = data.variant()?;
+                variant.struct_variant(PATCH_FIELDS[index], FieldsVisitor(index))
+            }
+        }
+
+        deserializer.deserialize_enum("Patch", PATCH_VARIANTS, PatchVisitor)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> self::serde::Deserialize<'de> for NodeId {
+    fn deserialize<D: self::serde::Deserializer<'de>>(deserializer: D) -> Result<NodeId, D::Error> {
+        u32::deserialize(deserializer).map(NodeId)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> self::serde::Deserialize<'de> for ListenerId {
+    fn deserialize<D: self::serde::Deserializer<'de>>(deserializer: D)
+        -> Result<ListenerId, D::Error>
+    {
+        u32::deserialize(deserializer).map(ListenerId)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> self::serde::Deserialize<'de> for Namespace {
+    fn deserialize<D: self::serde::Deserializer<'de>>(deserializer: D)
+        -> Result<Namespace, D::Error>
+    {
+        use self::serde::de::{Error, Unexpected};
+
+        let uri = String::deserialize(deserializer)?;
+        [Namespace::Html, Namespace::Svg, Namespace::MathMl].iter()
+            .find(|namespace| namespace.uri() == uri)
+            .cloned()
+            .ok_or_else(|| D::Error::invalid_value(Unexpected::Str(&uri), &"a namespace URI"))
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> self::serde::Deserialize<'de> for AttributeValue {
+    fn deserialize<D: self::serde::Deserializer<'de>>(deserializer: D)
+        -> Result<AttributeValue, D::Error>
+    {
+        use self::serde::de::{self, Unexpected};
+        use std::fmt;
+
+        struct ValueVisitor;
+
+        impl<'de> de::Visitor<'de> for ValueVisitor {
+            type Value = AttributeValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string, boolean or number")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<AttributeValue, E> {
+                Ok(AttributeValue::Bool(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<AttributeValue, E> {
+                Ok(AttributeValue::Int(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<AttributeValue, E> {
+                if value > i64::MAX as u64 {
+                    return Err(E::invalid_value(Unexpected::Unsigned(value), &self));
+                }
+                Ok(AttributeValue::Int(value as i64))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<AttributeValue, E> {
+                Ok(AttributeValue::Float(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<AttributeValue, E> {
+                Ok(AttributeValue::OwnedStr(value.to_owned()))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<AttributeValue, E> {
+                Ok(AttributeValue::OwnedStr(value))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
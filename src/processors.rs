@@ -1,4 +1,6 @@
 use {DomNode, Listener};
+use dom_node::MapMessages;
+use listener::MapperMarker;
 
 use opt_std::marker::PhantomData;
 
@@ -69,6 +71,45 @@ impl<Message> Listeners<Message> for EmptyListeners {
     }
 }
 
+/// Wrapper for a collection of `DomNode`s that converts the messages they publish using
+/// function `F`.
+///
+/// This is the `Children` type of `MapMessages`, created by `DomNode::map_messages`.
+#[repr(transparent)]
+pub struct MapNodes<M, N, T, F>(MapperMarker<M, N, F>, T);
+
+impl<M, N, T, F> MapNodes<M, N, T, F> {
+    pub(crate) fn from_ref(nodes: &T) -> &Self {
+        // Safe because `MapNodes` is a transparent wrapper around `T`
+        unsafe { &*(nodes as *const T as *const Self) }
+    }
+}
+
+impl<M, N, T, F> DomNodes<N> for MapNodes<M, N, T, F>
+    where T: DomNodes<M>, F: Fn(M) -> N + Copy
+{
+    fn process_all<'a, P: DomNodeProcessor<'a, N>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        self.1.process_all::<MapNodesProcessor<M, N, F, P>>(acc)
+    }
+}
+
+struct MapNodesProcessor<M, N, F, P>(PhantomData<(MapperMarker<M, N, F>, P)>);
+impl<'a, M, N, F, P> DomNodeProcessor<'a, M> for MapNodesProcessor<M, N, F, P>
+    where M: 'a, N: 'a, F: Fn(M) -> N + Copy + 'a, P: DomNodeProcessor<'a, N>
+{
+    type Acc = P::Acc;
+    type Error = P::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn process_mapped<'a, M, N, F, P, T>(acc: &mut P::Acc, node: &'a T) -> Result<(), P::Error>
+            where M: 'a, N: 'a, T: DomNode<M>, F: Fn(M) -> N + Copy + 'a, P: DomNodeProcessor<'a, N>
+        {
+            P::get_processor()(acc, MapMessages::<M, N, T, F>::from_ref(node))
+        }
+        process_mapped::<M, N, F, P, T>
+    }
+}
+
 impl<Message, T: DomNodes<Message>> DomNodes<Message> for Option<T> {
     fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
         if let Some(ref inner) = *self {
@@ -0,0 +1,67 @@
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+
+use {AttributeValue, KeyValue, RawHtml, raw_html};
+use processors::EmptyListeners;
+use tags::{Tag, attributes, script};
+
+/// The `id` of the `script` element containing the serialized state.
+///
+/// `web_render::serialized_state` reads the state from the element with this `id`.
+pub const STATE_SCRIPT_ID: &str = "domafic-state";
+
+/// A `script` element containing serialized application state.
+pub type StateScript<Message> = Tag<Message, RawHtml<String>, [KeyValue; 2], EmptyListeners>;
+
+/// Creates a `<script type="application/json">` element containing `state` serialized as JSON.
+///
+/// Include this element in server-rendered pages (for example, inside of a `head` element or by
+/// using `Document::with_state`) so that the client can start from the same state that produced
+/// the server's HTML. On the client, use `web_render::serialized_state` to read the state back.
+///
+/// Characters such as `<` are escaped in the JSON so that the contents of the state can't close
+/// the `script` element early.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::state::state_script;
+///
+/// let state = vec!["</script>".to_string()];
+/// let script = state_script::<(), _>(&state).unwrap();
+/// assert_eq!(
+///     r#"<script type="application/json" id="domafic-state">["\u003c/script\u003e"]</script>"#,
+///     script.to_string()
+/// );
+/// ```
+pub fn state_script<M, S: Serialize>(state: &S) -> Result<StateScript<M>, serde_json::Error> {
+    Ok(script((
+        attributes([
+            ("type", AttributeValue::Str("application/json")),
+            ("id", AttributeValue::Str(STATE_SCRIPT_ID)),
+        ]),
+        raw_html(to_script_json(state)?),
+    )))
+}
+
+/// Serializes `state` to JSON that can be safely embedded in a `script` element.
+pub(crate) fn to_script_json<S: Serialize>(state: &S) -> Result<String, serde_json::Error> {
+    let json = serde_json::to_string(state)?;
+
+    // These characters can only appear inside of JSON strings, where they can be replaced by
+    // their escaped forms without changing the value
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
@@ -7,7 +7,7 @@
 ///
 /// TODO
 
-use {DomNode, DomNodes, DomValue, KeyValue, Listeners};
+use {AttributeValue, DomNode, DomNodes, DomValue, KeyValue, Listeners, Namespace};
 use processors::{DomNodeProcessor, EmptyListeners};
 
 use opt_std::marker::PhantomData;
@@ -52,7 +52,7 @@ type EmptyAttrs = [KeyValue; 0];
 ///     // We need to manually mark the message type since it can't be inferred
 ///     PhantomData::<()>
 /// ));
-/// assert_eq!(div_with_attrs.get_attribute(0), Some(&("key", Str("value"))));
+/// assert_eq!(div_with_attrs.get_attribute(0), Some(("key", &Str("value"))));
 /// ```
 pub fn attributes<A: AsRef<[KeyValue]>>(attrs: A) -> Attrs<A> {
     Attrs(attrs)
@@ -165,14 +165,19 @@ impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>>
 }
 
 /// A tag element, such as `div` or `span`.
+///
+/// The tag name is usually a `&'static str`, but custom elements created with `custom_tag` may
+/// use any `Name` that can be viewed as a `str`, such as an owned `String`.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct Tag<
     Message,
     Children: DomNodes<Message>,
     Attributes: AsRef<[KeyValue]>,
-    L: Listeners<Message>>
+    L: Listeners<Message>,
+    Name: AsRef<str> = &'static str>
 {
-    tagname: &'static str,
+    tagname: Name,
+    namespace: Namespace,
     children: Children,
     key: Option<u32>,
     attributes: Attributes,
@@ -184,19 +189,22 @@ impl<
     M,
     C: DomNodes<M>,
     A: AsRef<[KeyValue]>,
-    L: Listeners<M>> DomNodes<M> for Tag<M, C, A, L>
+    L: Listeners<M>,
+    N: AsRef<str>> DomNodes<M> for Tag<M, C, A, L, N>
 {
     fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
         P::get_processor()(acc, self)
     }
 }
-impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>> DomNode<M> for Tag<M, C, A, L> {
+impl<M, C, A, L, N> DomNode<M> for Tag<M, C, A, L, N>
+    where C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>, N: AsRef<str>
+{
     type Children = C;
     type Listeners = L;
-    type WithoutListeners = Tag<M, C, A, EmptyListeners>;
+    type WithoutListeners = Tag<M, C, A, EmptyListeners, N>;
     fn key(&self) -> Option<u32> { self.key }
-    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
-        self.attributes.as_ref().get(index)
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        self.attributes.as_ref().get(index).map(|&(name, ref value)| (name, value))
     }
     fn children(&self) -> &Self::Children {
         &self.children
@@ -208,10 +216,11 @@ impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>> DomNode<M> for Ta
         (&self.children, &self.listeners)
     }
     fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
-        let Tag { tagname, children, key, attributes, listeners, msg_marker } = self;
+        let Tag { tagname, namespace, children, key, attributes, listeners, msg_marker } = self;
         (
             Tag {
                 tagname: tagname,
+                namespace: namespace,
                 children: children,
                 key: key,
                 attributes: attributes,
@@ -221,9 +230,10 @@ impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>> DomNode<M> for Ta
             listeners
         )
     }
-    fn value(&self) -> DomValue {
+    fn value(&self) -> DomValue<'_> {
         DomValue::Element {
-            tag: self.tagname,
+            tag: self.tagname.as_ref(),
+            namespace: self.namespace,
         }
     }
 }
@@ -231,11 +241,12 @@ impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>> DomNode<M> for Ta
 #[cfg(any(feature = "use_std", test))]
 use std::fmt;
 #[cfg(any(feature = "use_std", test))]
-impl<M, C, A, L> fmt::Display for Tag<M, C, A, L>
+impl<M, C, A, L, N> fmt::Display for Tag<M, C, A, L, N>
     where
     C: DomNodes<M>,
     A: AsRef<[KeyValue]>,
-    L: Listeners<M>
+    L: Listeners<M>,
+    N: AsRef<str>
 {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         self.displayable().fmt(formatter)
@@ -243,15 +254,168 @@ impl<M, C, A, L> fmt::Display for Tag<M, C, A, L>
 }
 
 
+/// Creates a custom element, such as `my-date-picker`, with a tag name chosen at runtime.
+///
+/// `name` may be any type that can be viewed as a `str`, including an owned `String`. Like the
+/// other tag functions, `properties` may be any combination of attributes, listeners and
+/// children.
+///
+/// Panics if `name` is not a valid custom element name. Use `try_custom_tag` for names that
+/// come from data or user input.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::tags::custom_tag;
+/// use std::marker::PhantomData;
+///
+/// let prefix = "my";
+/// let picker = custom_tag(format!("{}-date-picker", prefix), (
+///     "Pick a date",
+///     // We need to manually mark the message type since it can't be inferred
+///     PhantomData::<()>,
+/// ));
+/// assert_eq!("<my-date-picker>Pick a date</my-date-picker>", picker.to_string());
+/// ```
+pub fn custom_tag<M, N, C, A, L, T>(name: N, properties: T) -> Tag<M, C, A, L, N>
+    where
+    N: AsRef<str>,
+    C: DomNodes<M>,
+    A: AsRef<[KeyValue]>,
+    L: Listeners<M>,
+    T: Into<TagProperties<M, C, A, L>>
+{
+    assert!(is_valid_custom_element_name(name.as_ref()),
+        "Invalid custom element name: {}", name.as_ref());
+    try_custom_tag(name, properties).unwrap()
+}
+
+/// Creates a custom element like `custom_tag`, returning `None` if `name` is not a valid custom
+/// element name (see `is_valid_custom_element_name`).
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::tags::try_custom_tag;
+/// use std::marker::PhantomData;
+///
+/// let marker = PhantomData::<()>;
+/// assert!(try_custom_tag("user-card", marker).is_some());
+/// assert!(try_custom_tag("usercard", marker).is_none());
+/// ```
+pub fn try_custom_tag<M, N, C, A, L, T>(name: N, properties: T) -> Option<Tag<M, C, A, L, N>>
+    where
+    N: AsRef<str>,
+    C: DomNodes<M>,
+    A: AsRef<[KeyValue]>,
+    L: Listeners<M>,
+    T: Into<TagProperties<M, C, A, L>>
+{
+    if !is_valid_custom_element_name(name.as_ref()) {
+        return None;
+    }
+
+    let TagProperties {
+        children,
+        key,
+        attributes,
+        listeners,
+        msg_marker,
+    } = properties.into();
+
+    Some(Tag {
+        tagname: name,
+        namespace: Namespace::Html,
+        children,
+        key,
+        attributes,
+        listeners,
+        msg_marker,
+    })
+}
+
+/// Returns whether or not `name` is a valid custom element name according to the HTML
+/// specification.
+///
+/// Valid names start with a lowercase ASCII letter, contain a hyphen, do not contain uppercase
+/// ASCII letters or characters such as whitespace and `<`, and are not one of the reserved names
+/// used by SVG and MathML (such as `font-face`).
+pub fn is_valid_custom_element_name(name: &str) -> bool {
+    const RESERVED_NAMES: &[&str] = &[
+        "annotation-xml", "color-profile", "font-face", "font-face-src", "font-face-uri",
+        "font-face-format", "font-face-name", "missing-glyph",
+    ];
+
+    fn is_name_char(c: char) -> bool {
+        matches!(c,
+            '-' | '.' | '_' | '0'..='9' | 'a'..='z' | '\u{B7}' |
+            '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{37D}' |
+            '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}' | '\u{203F}'..='\u{2040}' |
+            '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}' |
+            '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+    }
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some('a'..='z') => {},
+        _ => return false,
+    }
+
+    name.contains('-') &&
+        chars.all(is_name_char) &&
+        !RESERVED_NAMES.contains(&name)
+}
+
+/// Returns whether or not `name` is a valid HTML attribute name.
+///
+/// Valid names are non-empty and do not contain whitespace, control characters, quotes, `>`,
+/// `/` or `=`. Names with a namespace prefix, such as `xlink:href`, are allowed.
+pub fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty() &&
+        !name.chars().any(|c| c.is_whitespace() || c.is_control() ||
+            matches!(c, '"' | '\'' | '>' | '/' | '=' | '<' | '\u{FDD0}'..='\u{FDEF}' |
+                '\u{FFFE}' | '\u{FFFF}'))
+}
+
+/// Returns whether or not `tag` is the name of an HTML void element, such as `br` or `input`.
+///
+/// Void elements cannot have any children and are written without a closing tag.
+pub fn is_void_element(tag: &str) -> bool {
+    matches!(tag,
+        "area" | "base" | "basefont" | "bgsound" | "br" | "col" | "embed" | "frame" | "hr" |
+        "img" | "input" | "keygen" | "link" | "meta" | "param" | "source" | "track" | "wbr")
+}
+
+/// Returns whether or not `tag` is the name of an HTML element that is laid out inline with
+/// text by default, such as `span` or `a`.
+///
+/// Whitespace between inline elements is significant, so `HtmlFormat` never adds line breaks
+/// between them when pretty-printing.
+pub fn is_inline_element(tag: &str) -> bool {
+    matches!(tag,
+        "a" | "abbr" | "acronym" | "audio" | "b" | "bdi" | "bdo" | "big" | "br" | "button" |
+        "canvas" | "cite" | "code" | "data" | "datalist" | "del" | "dfn" | "em" | "embed" |
+        "font" | "i" | "iframe" | "img" | "input" | "ins" | "kbd" | "label" | "map" | "mark" |
+        "meter" | "object" | "output" | "picture" | "progress" | "q" | "rp" | "rt" |
+        "ruby" | "s" | "samp" | "select" | "small" | "span" | "strike" | "strong" | "sub" |
+        "sup" | "textarea" | "time" | "tt" | "u" | "var" | "video" | "wbr")
+}
+
 macro_rules! impl_tags {
-    ($($tagname:ident),*) => { $(
+    ($($tagname:ident),*) => {
+        impl_namespaced_tags!(Namespace::Html; $($tagname => stringify!($tagname)),*);
+    }
+}
+
+macro_rules! impl_namespaced_tags {
+    ($namespace:expr; $($fn_name:ident => $tagname:expr),*) => { $(
         /// Creates a tag of the given type.
         ///
         /// Note the use of `Into<TagProperties>`. This allows for a wide variety of input
         /// parameters such as `div(())`, `div(...children...)`,
         /// `div((...attributes..., ...children..))`, `div((...attributes..., ...listeners...))`
         /// and more.
-        pub fn $tagname<
+        pub fn $fn_name<
             M,
             C: DomNodes<M>,
             A: AsRef<[KeyValue]>,
@@ -269,12 +433,13 @@ macro_rules! impl_tags {
             } = properties.into();
 
             Tag {
-                tagname: stringify!($tagname),
-                children: children,
-                key: key,
-                attributes: attributes,
-                listeners: listeners,
-                msg_marker: msg_marker,
+                tagname: $tagname,
+                namespace: $namespace,
+                children,
+                key,
+                attributes,
+                listeners,
+                msg_marker,
             }
         }
     )* }
@@ -291,3 +456,130 @@ impl_tags!(
     source, span, strike, strong, style, sub, summary, sup, table, tbody, td, textarea, tfoot,
     th, thead, time, title, tr, track, tt, u, ul, var, video, wbr
 );
+
+/// SVG tags, such as `svg`, `path` or `circle`.
+///
+/// These elements are created in the SVG namespace. Tag names that aren't valid Rust
+/// identifiers are written in snake case (e.g. `linear_gradient` creates `linearGradient`), and
+/// `use` is available as `use_`.
+///
+/// Attributes in the XLink and XML namespaces, such as `xlink:href`, are written with their
+/// usual prefix.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::tags::{attributes, div};
+/// use domafic::tags::svg::{svg, circle};
+/// use domafic::AttributeValue::{Int, Str};
+/// use std::marker::PhantomData;
+///
+/// let chart = div(svg((
+///     attributes([("viewBox", Str("0 0 10 10"))]),
+///     (
+///         circle(attributes([("cx", Int(5)), ("cy", Int(5)), ("r", Int(4))])),
+///         // We need to manually mark the message type since it can't be inferred
+///         PhantomData::<()>,
+///     )
+/// )));
+///
+/// assert_eq!(
+///     r#"<div><svg viewBox="0 0 10 10"><circle cx="5" cy="5" r="4" /></svg></div>"#,
+///     chart.to_string()
+/// );
+/// ```
+pub mod svg {
+    use {DomNodes, KeyValue, Listeners, Namespace};
+    use super::{Tag, TagProperties};
+
+    impl_namespaced_tags!(Namespace::Svg;
+        a => "a",
+        animate => "animate",
+        animate_motion => "animateMotion",
+        animate_transform => "animateTransform",
+        circle => "circle",
+        clip_path => "clipPath",
+        defs => "defs",
+        desc => "desc",
+        ellipse => "ellipse",
+        fe_blend => "feBlend",
+        fe_color_matrix => "feColorMatrix",
+        fe_composite => "feComposite",
+        fe_flood => "feFlood",
+        fe_gaussian_blur => "feGaussianBlur",
+        fe_merge => "feMerge",
+        fe_merge_node => "feMergeNode",
+        fe_offset => "feOffset",
+        filter => "filter",
+        foreign_object => "foreignObject",
+        g => "g",
+        image => "image",
+        line => "line",
+        linear_gradient => "linearGradient",
+        marker => "marker",
+        mask => "mask",
+        metadata => "metadata",
+        mpath => "mpath",
+        path => "path",
+        pattern => "pattern",
+        polygon => "polygon",
+        polyline => "polyline",
+        radial_gradient => "radialGradient",
+        rect => "rect",
+        set => "set",
+        stop => "stop",
+        svg => "svg",
+        switch => "switch",
+        symbol => "symbol",
+        text => "text",
+        text_path => "textPath",
+        title => "title",
+        tspan => "tspan",
+        use_ => "use",
+        view => "view"
+    );
+}
+
+/// MathML tags, such as `math`, `mi` or `mfrac`.
+///
+/// These elements are created in the MathML namespace. `annotation-xml` is available as
+/// `annotation_xml`.
+pub mod mathml {
+    use {DomNodes, KeyValue, Listeners, Namespace};
+    use super::{Tag, TagProperties};
+
+    impl_namespaced_tags!(Namespace::MathMl;
+        annotation => "annotation",
+        annotation_xml => "annotation-xml",
+        maction => "maction",
+        math => "math",
+        menclose => "menclose",
+        merror => "merror",
+        mfenced => "mfenced",
+        mfrac => "mfrac",
+        mi => "mi",
+        mmultiscripts => "mmultiscripts",
+        mn => "mn",
+        mo => "mo",
+        mover => "mover",
+        mpadded => "mpadded",
+        mphantom => "mphantom",
+        mprescripts => "mprescripts",
+        mroot => "mroot",
+        mrow => "mrow",
+        ms => "ms",
+        mspace => "mspace",
+        msqrt => "msqrt",
+        mstyle => "mstyle",
+        msub => "msub",
+        msubsup => "msubsup",
+        msup => "msup",
+        mtable => "mtable",
+        mtd => "mtd",
+        mtext => "mtext",
+        mtr => "mtr",
+        munder => "munder",
+        munderover => "munderover",
+        semantics => "semantics"
+    );
+}
@@ -0,0 +1,172 @@
+use {AttributeValue, DomNode, DomNodes, DomValue, KeyValue, Namespace};
+use processors::{DomNodeProcessor, EmptyListeners};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::convert::Infallible;
+use std::fmt;
+
+/// An owned snapshot of a rendered `DomNode` tree.
+///
+/// Unlike the `DomNode`s it is created from, a `VNode` can be inspected, cloned, compared and
+/// stored. Listeners are not part of a `VNode`, so it can be used with any message type.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::AttributeValue::Str;
+/// use domafic::on;
+/// use domafic::tags::{attributes, button, div};
+/// use domafic::vnode::VNode;
+///
+/// let render = |count: i32| div((
+///     attributes([("class", Str("counter"))]),
+///     button((on("click", |_| ()), count.to_string())),
+/// ));
+///
+/// let first = VNode::from_node(&render(1));
+/// assert_eq!(first, VNode::from_node(&render(1)));
+/// assert!(first != VNode::from_node(&render(2)));
+///
+/// if let VNode::Element { ref tag, ref children, .. } = first {
+///     assert_eq!("div", tag);
+///     assert_eq!(1, children.len());
+/// }
+/// assert_eq!("<div class=\"counter\"><button>1</button></div>", first.to_string());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum VNode {
+    /// An element, such as a `div`
+    Element {
+        /// Tag name, such as `div`
+        tag: String,
+        /// Namespace of the element
+        namespace: Namespace,
+        /// Key used to identify the element among its siblings
+        key: Option<u32>,
+        /// Attributes, in the order they were declared
+        attributes: Vec<(String, AttributeValue)>,
+        /// DOM properties, in the order they were declared
+        properties: Vec<KeyValue>,
+        /// Child nodes
+        children: Vec<VNode>,
+    },
+
+    /// A text node
+    Text(String),
+
+    /// Trusted HTML markup, created by `raw_html`
+    RawHtml(String),
+}
+
+impl VNode {
+    /// Converts `node` and its children into a `VNode`.
+    pub fn from_node<M, T: DomNode<M>>(node: &T) -> VNode {
+        let mut nodes = Vec::with_capacity(1);
+        match node.process_all::<VNodeBuilder>(&mut nodes) {
+            Ok(()) => {},
+            Err(never) => match never {},
+        }
+        // Flush points are skipped, so a lone flush point becomes empty text
+        nodes.pop().unwrap_or_else(|| VNode::Text(String::new()))
+    }
+}
+
+static NO_CHILDREN: Vec<VNode> = Vec::new();
+
+impl<M> DomNodes<M> for VNode {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M> DomNode<M> for VNode {
+    type Children = Vec<VNode>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> {
+        match *self {
+            VNode::Element { key, .. } => key,
+            VNode::Text(_) | VNode::RawHtml(_) => None,
+        }
+    }
+    fn get_attribute(&self, index: usize) -> Option<(&str, &AttributeValue)> {
+        match *self {
+            VNode::Element { ref attributes, .. } =>
+                attributes.get(index).map(|(name, value)| (name.as_str(), value)),
+            VNode::Text(_) | VNode::RawHtml(_) => None,
+        }
+    }
+    fn get_property(&self, index: usize) -> Option<&KeyValue> {
+        match *self {
+            VNode::Element { ref properties, .. } => properties.get(index),
+            VNode::Text(_) | VNode::RawHtml(_) => None,
+        }
+    }
+    fn children(&self) -> &Self::Children {
+        match *self {
+            VNode::Element { ref children, .. } => children,
+            VNode::Text(_) | VNode::RawHtml(_) => &NO_CHILDREN,
+        }
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        &EmptyListeners
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (DomNode::<M>::children(self), &EmptyListeners)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> {
+        match *self {
+            VNode::Element { ref tag, namespace, .. } => DomValue::Element { tag, namespace },
+            VNode::Text(ref text) => DomValue::Text(text),
+            VNode::RawHtml(ref markup) => DomValue::RawHtml(markup),
+        }
+    }
+}
+
+impl fmt::Display for VNode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        DomNode::<()>::write_html_fmt(self, formatter)
+    }
+}
+
+/// Type to use for converting a collection of `DomNode`s into `VNode`s.
+///
+/// Each node processed is appended to the accumulator. This type should not ever need to be
+/// instantiated. Instead, simply name the type in calls to
+/// `DomNodes::process_all::<VNodeBuilder>(...)`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct VNodeBuilder;
+impl<'a, M> DomNodeProcessor<'a, M> for VNodeBuilder {
+    type Acc = Vec<VNode>;
+    type Error = Infallible;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(nodes: &mut Vec<VNode>, node: &T) -> Result<(), Infallible> {
+            nodes.push(match node.value() {
+                // Flush points only matter when streaming HTML
+                DomValue::FlushPoint => return Ok(()),
+                DomValue::Element { tag, namespace } => {
+                    let mut children = Vec::new();
+                    node.children().process_all::<VNodeBuilder>(&mut children)?;
+                    VNode::Element {
+                        tag: tag.to_string(),
+                        namespace,
+                        key: node.key(),
+                        attributes: node.attributes()
+                            .map(|(name, value)| (name.to_string(), value.clone()))
+                            .collect(),
+                        properties: node.properties().cloned().collect(),
+                        children,
+                    }
+                }
+                DomValue::Text(text) => VNode::Text(text.to_string()),
+                DomValue::RawHtml(markup) => VNode::RawHtml(markup.to_string()),
+            });
+            Ok(())
+        }
+        add_node
+    }
+}
@@ -0,0 +1,818 @@
+extern crate libc;
+
+use super::{Updater, Renderer};
+use super::private::{HttpRequest, HttpResponseHandler, JsElementId, WebDocument, WebElement};
+use super::private::{deliver_event, deliver_http_result, property_kind};
+use {AttributeValue, DomNode, DomValue, Event, KeyValue, Namespace};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::ffi::{CString, CStr};
+use std::os::raw::c_void;
+use std::{mem, slice, str};
+
+/// Issues an HTTP request, passing the response to `handler` and then updating the application
+pub(super) fn http<D, M, U, R, S>(
+    system_c_ptr: *mut c_void,
+    http_request: HttpRequest,
+    handler: Box<HttpResponseHandler<Message=M>>,
+)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+{
+    unsafe {
+        let HttpRequest { method, headers, url, body, timeout_millis } = http_request;
+        let method_cstring = CString::new(method).unwrap();
+        let url_cstring = CString::new(url).unwrap();
+        let body_cstring = CString::new(body).unwrap();
+
+        let header_key_cstrings: Vec<CString> =
+        headers.iter().map(|header| CString::new(header.0).unwrap()).collect();
+
+        let header_key_pointers: Vec<libc::c_int> =
+        header_key_cstrings.iter().map(|cstring|
+            cstring.as_ptr() as libc::c_int).collect();
+
+        let header_value_cstrings: Vec<CString> =
+        headers.iter().map(|header| CString::new(header.1).unwrap()).collect();
+
+        let header_value_pointers: Vec<libc::c_int> =
+        header_value_cstrings.iter().map(|cstring|
+            cstring.as_ptr() as libc::c_int).collect();
+
+        let handler_ptr = Box::into_raw(handler);
+
+        let (handler_data_ptr, handler_vtable_ptr):
+            (*const c_void, *const c_void) =
+            mem::transmute(handler_ptr);
+
+        const JS: &'static [u8] = b"\
+            var handler_fn_ptr = $0;\
+            var app_system = $1;\
+            var method = UTF8ToString($2);\
+            var url = UTF8ToString($3);\
+            var body = UTF8ToString($4);\
+            var header_len = $5;\
+            var header_key_ptr = $6;\
+            var header_value_ptr = $7;\
+            var timeout = $8;\
+            var handler_data_ptr = $9;\
+            var handler_vtable_ptr = $10;\
+            var xhr = new XMLHttpRequest();\
+            var error_fn = function(error_sig) { return function() {\
+                Runtime.dynCall('viiiiiiii', handler_fn_ptr, [error_sig, app_system, handler_data_ptr, handler_vtable_ptr, 0, 0, 0, 0]);\
+            } };\
+            xhr.addEventListener('timeout', error_fn(1));\
+            xhr.addEventListener('error', error_fn(2));\
+            xhr.addEventListener('load', function() {\
+                var stack = Runtime.stackSave();\
+                var status_code = xhr.status;\
+                var status_text = allocate(\
+                    intArrayFromString(xhr.statusText), 'i8', ALLOC_STACK\
+                );\
+                var response_headers = allocate(\
+                    intArrayFromString(xhr.getAllResponseHeaders()), 'i8', ALLOC_STACK\
+                );\
+                var response_body =\
+                    allocate(intArrayFromString(xhr.responseText), 'i8', ALLOC_STACK);\
+                Runtime.dynCall('viiiiiiii', handler_fn_ptr, [0, app_system, handler_data_ptr, handler_vtable_ptr, status_code, status_text, response_body, response_headers]);\
+                Runtime.stackRestore(stack);\
+            });\
+            try { xhr.open(method, url, true); } catch (e) { error_fn(3); return; }\
+            for (var i = 0; i < header_len; i++) {\
+                var header_key = UTF8ToString(getValue(header_key_ptr + (i * 4), '*'));\
+                var header_value = UTF8ToString(getValue(header_value_ptr + (i * 4), '*'));\
+                xhr.setRequestHeader(header_key, header_value);\
+            }\
+            xhr.responseType = 'text';\
+            if (timeout != 0) { xhr.timeout = timeout; }\
+            xhr.send(body);\
+        \0";
+
+        emscripten_asm_const_int(
+            &JS[0] as *const _ as *const libc::c_char,
+            handle_http_result::<D, M, U, R, S> as *const c_void,
+            system_c_ptr as *const c_void,
+            method_cstring.as_ptr() as libc::c_int,
+            url_cstring.as_ptr() as libc::c_int,
+            body_cstring.as_ptr() as libc::c_int,
+            header_key_pointers.len() as libc::c_int,
+            header_key_pointers.as_ptr() as *const _ as *const libc::c_char,
+            header_value_pointers.as_ptr() as *const _ as *const libc::c_char,
+            timeout_millis.unwrap_or(0) as libc::c_int,
+            handler_data_ptr,
+            handler_vtable_ptr,
+        );
+    }
+}
+
+unsafe extern fn handle_http_result<D, M, U, R, S>
+(
+    error_sig: libc::c_int,
+    system_c_ptr: *mut c_void,
+    handler_data_ptr: *const c_void,
+    handler_vtable_ptr: *const c_void,
+    status_code: u16,
+    status_text: *const libc::c_char,
+    body: *const libc::c_char,
+    headers_ptr: *const libc::c_char
+)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>,
+{
+    let handler_ptr: *mut HttpResponseHandler<Message=M> =
+        mem::transmute((handler_data_ptr, handler_vtable_ptr));
+    let handler = Box::from_raw(handler_ptr);
+
+    let status_text = str::from_utf8(CStr::from_ptr(status_text).to_bytes()).unwrap();
+    let (body, headers) = if error_sig == 0 {
+        (
+            str::from_utf8(CStr::from_ptr(body).to_bytes()).unwrap(),
+            str::from_utf8(CStr::from_ptr(headers_ptr).to_bytes()).unwrap(),
+        )
+    } else {
+        ("", "")
+    };
+
+    deliver_http_result::<D, M, U, R, S>(
+        system_c_ptr, handler, error_sig, status_code, status_text, body, headers);
+}
+
+extern "C" {
+    fn emscripten_asm_const_int(s: *const libc::c_char, ...) -> libc::c_int;
+    fn emscripten_pause_main_loop();
+    fn emscripten_set_main_loop(m: extern fn(), fps: libc::c_int, infinite: libc::c_int);
+}
+
+pub(super) fn web_init() {
+    const JS: &'static [u8] = b"\
+        if('undefined'===typeof __domafic_pool){\
+            console.log('Intializing __domafic_pool');\
+            __domafic_pool=[];\
+            __domafic_pool_free=[];\
+            __domafic_attr_ns={\
+                xlink:'http://www.w3.org/1999/xlink',\
+                xml:'http://www.w3.org/XML/1998/namespace',\
+                xmlns:'http://www.w3.org/2000/xmlns/'\
+            };\
+        }\
+    \0";
+
+    unsafe {
+        emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+    }
+}
+
+extern fn pause_main_web_loop() {
+    unsafe { emscripten_pause_main_loop(); }
+}
+
+pub(super) fn run_main_web_loop() -> ! {
+    unsafe { emscripten_set_main_loop(pause_main_web_loop, 0, 1); }
+    panic!("Emscripten main loop should never return")
+}
+
+impl WebDocument {
+    pub(super) fn element_from_selector(&self, selector: &str) -> Option<WebElement> {
+        let id = {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = document.querySelector(UTF8ToString($0));\
+                    if (!elem) {return -1;}\
+                    var index = __domafic_pool_free.pop();\
+                    if (index) { __domafic_pool[index] = elem; return index; }\
+                    return __domafic_pool.push(elem) - 1;\
+                \0";
+                let selector_cstring = CString::new(selector).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    selector_cstring.as_ptr() as libc::c_int
+                )
+            }
+        };
+        if id < 0 { None } else { Some(WebElement(id)) }
+    }
+
+    pub(super) fn create_element(&self, tagname: &str, namespace: Namespace) -> Option<WebElement> {
+        let id = {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = $1 ?\
+                        document.createElementNS(UTF8ToString($1), UTF8ToString($0)) :\
+                        document.createElement(UTF8ToString($0));\
+                    if (!elem) {return -1;}\
+                    var index = __domafic_pool_free.pop();\
+                    if (index) { __domafic_pool[index] = elem; return index; }\
+                    return __domafic_pool.push(elem) - 1;\
+                \0";
+                let tagname_cstring = CString::new(tagname).unwrap();
+                let namespace_cstring = match namespace {
+                    Namespace::Html => None,
+                    _ => Some(CString::new(namespace.uri()).unwrap()),
+                };
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    tagname_cstring.as_ptr() as libc::c_int,
+                    namespace_cstring.as_ref().map_or(0, |ns| ns.as_ptr() as libc::c_int)
+                )
+            }
+        };
+        if id < 0 { None } else { Some(WebElement(id)) }
+    }
+
+    pub(super) fn create_text_node(&self, text: &str) -> Option<WebElement> {
+        let id = {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var text = document.createTextNode(UTF8ToString($0));\
+                    if (!text) {return -1;}\
+                    var elem = document.createElement('span');\
+                    elem.appendChild(text);\
+                    var index = __domafic_pool_free.pop();\
+                    if (index) { __domafic_pool[index] = elem; return index; }\
+                    return __domafic_pool.push(elem) - 1;\
+                \0";
+                let text_cstring = CString::new(text).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    text_cstring.as_ptr() as libc::c_int
+                )
+            }
+        };
+        if id < 0 { None } else { Some(WebElement(id)) }
+    }
+
+    pub(super) fn create_raw_html(&self, markup: &str) -> Option<WebElement> {
+        let id = {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = document.createElement('span');\
+                    if (!elem) {return -1;}\
+                    elem.innerHTML = UTF8ToString($0);\
+                    var index = __domafic_pool_free.pop();\
+                    if (index) { __domafic_pool[index] = elem; return index; }\
+                    return __domafic_pool.push(elem) - 1;\
+                \0";
+                let markup_cstring = CString::new(markup).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    markup_cstring.as_ptr() as libc::c_int
+                )
+            }
+        };
+        if id < 0 { None } else { Some(WebElement(id)) }
+    }
+}
+
+unsafe extern fn handle_event<D, M, U, R, S>(
+    system_c_ptr: *mut c_void,
+
+    type_str_ptr: *const libc::c_char,
+    target_value_ptr: *const libc::c_char,
+    client_x: libc::c_int,
+    client_y: libc::c_int,
+    offset_x: libc::c_int,
+    offset_y: libc::c_int,
+    which_keycode: libc::c_int,
+    shift_key: libc::c_int,
+    alt_key: libc::c_int,
+    ctrl_key: libc::c_int,
+    meta_key: libc::c_int,
+
+    bubbles: libc::c_int,
+    path_ptr: *const libc::c_int,
+    path_len: libc::c_int,
+)
+    where
+    (D, U, R, S): Sized,
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+
+{
+    let type_str = if (type_str_ptr as usize) != 0 {
+        str::from_utf8(CStr::from_ptr(type_str_ptr).to_bytes()).ok()
+    } else {
+        None
+    };
+    let target_value = if (target_value_ptr as usize) != 0 {
+        str::from_utf8(CStr::from_ptr(target_value_ptr).to_bytes()).ok()
+    } else {
+        None
+    };
+    let event = Event {
+        type_str: type_str,
+        target_value: target_value,
+        client_x: client_x as i32,
+        client_y: client_y as i32,
+        offset_x: offset_x as i32,
+        offset_y: offset_y as i32,
+        which_keycode: which_keycode as i32,
+        shift_key: shift_key == 1,
+        alt_key: alt_key == 1,
+        ctrl_key: ctrl_key == 1,
+        meta_key: meta_key == 1,
+    };
+
+    let path = slice::from_raw_parts(path_ptr, path_len as usize).iter()
+        .map(|&index| index as usize)
+        .collect::<Vec<_>>();
+
+    deliver_event::<D, M, U, R, S>(system_c_ptr, event, &path, bubbles == 1);
+}
+
+/// Listens for events of type `event_name` on the element `root`, passing each one to
+/// `deliver_event` along with the path from `root` to the event's target.
+///
+/// The listener is called during the capture phase, so that events which don't bubble are
+/// received too. Requires that `system_ptr` is valid and that `root` is a valid `WebElement` id
+/// throughout the duration of time that it is possible for this callback to be triggered.
+pub(super) unsafe fn delegate<D, M, U, R, S>(
+    root: JsElementId,
+    event_name: &str,
+    system_ptr: *mut c_void,
+)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+{
+    const JS: &'static [u8] = b"\
+        var root = __domafic_pool[$0];\
+        root.addEventListener(UTF8ToString($1), function(event) {\
+            event = event || window.event;\
+            var path = [];\
+            var node = event.target;\
+            if (node && node.nodeType != 1) { node = node.parentNode; }\
+            while (node !== root) {\
+                var parent = node && node.parentNode;\
+                if (!parent) { return; }\
+                path.unshift(Array.prototype.indexOf.call(parent.children, node));\
+                node = parent;\
+            }\
+            var stack = Runtime.stackSave();\
+            var typeStr = event.type ? allocate(intArrayFromString(event.type), 'i8', ALLOC_STACK) : 0;\
+            var targetValue = (event.target && event.target.value) ? allocate(intArrayFromString(event.target.value), 'i8', ALLOC_STACK) : 0;\
+            Runtime.dynCall('viiiiiiiiiiiiiii', $2, [$3,\
+            typeStr,\
+            targetValue,\
+            Math.floor(event.clientX || 0), Math.floor(event.clientY || 0),\
+            Math.floor(event.offsetX || 0), Math.floor(event.offsetY || 0),\
+            event.which || event.keyCode || 0,\
+            event.shiftKey ? 1 : 0,\
+            event.altKey ? 1 : 0,\
+            event.ctrlKey ? 1 : 0,\
+            event.metaKey ? 1 : 0,\
+            event.bubbles ? 1 : 0,\
+            allocate(path, 'i32', ALLOC_STACK),\
+            path.length\
+            ]);\
+            Runtime.stackRestore(stack);\
+        }, true);\
+    \0";
+
+    let event_name_cstring = CString::new(event_name).unwrap();
+    emscripten_asm_const_int(
+        &JS[0] as *const _ as *const libc::c_char,
+        root,
+        event_name_cstring.as_ptr() as libc::c_int,
+        handle_event::<D, M, U, R, S> as *const c_void,
+        system_ptr as *const c_void
+    );
+}
+
+impl WebElement {
+
+    #[allow(dead_code)]
+    pub(super) fn append(&self, child: &WebElement) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                __domafic_pool[$0].appendChild(__domafic_pool[$1]);\
+            \0";
+
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                child.0
+            );
+        }
+    }
+
+    pub(super) fn insert(&self, index: usize, child: &WebElement) {
+        let err = unsafe {
+            const JS: &'static [u8] = b"\
+                var parent = __domafic_pool[$0];\
+                if ($2 > parent.children.length) { return -1; }\
+                if ($2 == parent.children.length) {\
+                    parent.appendChild(__domafic_pool[$1]);\
+                } else {\
+                    parent.insertBefore(__domafic_pool[$1], parent.children[$2]);\
+                }\
+                return 0;\
+            \0";
+
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                child.0,
+                index as libc::c_int
+            )
+        };
+
+        // Must panic on error because failure to properly add/remove nodes
+        // containing listeners can cause memory unsafety
+        if err < 0 { panic!("Attempted to insert child DOM element out of bounds") }
+    }
+
+    pub(super) fn move_child(&self, old_index: usize, new_index: usize) {
+        let err = unsafe {
+            const JS: &'static [u8] = b"\
+                var parent = __domafic_pool[$0];\
+                if ($1 >= parent.children.length) { return -1; }\
+                if ($2 >= parent.children.length) { return -1; }\
+                var element = parent.removeChild(parent.children[$1]);\
+                if ($2 == parent.children.length) {\
+                    parent.appendChild(element);\
+                } else {\
+                    parent.insertBefore(element, parent.children[$2]);\
+                }\
+                return 0;\
+            \0";
+
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                old_index as libc::c_int,
+                new_index as libc::c_int
+            )
+        };
+
+        // Must panic on error because failure to properly add/remove nodes
+        // containing listeners can cause memory unsafety
+        if err < 0 { panic!("Attempted to move child DOM element out of bounds") }
+    }
+
+    pub(super) fn remove_all_children(&self) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                while (elem.hasChildNodes()) { elem.removeChild(elem.lastChild); }\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+            );
+        }
+    }
+
+    /// Finds the server-rendered node matching `value` following the first `index` children,
+    /// which have already been claimed, and claims it.
+    ///
+    /// Whitespace-only text nodes and comments that are skipped over are removed. Text nodes
+    /// are split if they contain the text of multiple adjacent nodes, and are wrapped in a
+    /// `span` to match `create_text_node`.
+    pub(super) fn claim_child(&self, index: usize, value: DomValue) -> Option<WebElement> {
+        let (kind, text, namespace) = match value {
+            DomValue::Element { tag, namespace } => (0, tag, match namespace {
+                Namespace::Html => None,
+                _ => Some(CString::new(namespace.uri()).unwrap()),
+            }),
+            DomValue::Text(text) => (1, text, None),
+            DomValue::RawHtml(markup) => (2, markup, None),
+            DomValue::FlushPoint => return None,
+        };
+
+        let id = unsafe {
+            const JS: &'static [u8] = b"\
+                var parent = __domafic_pool[$0];\
+                var kind = $2;\
+                var value = UTF8ToString($3);\
+                var ns = $4 ? UTF8ToString($4) : 'http://www.w3.org/1999/xhtml';\
+                var node = $1 == 0 ? parent.firstChild : parent.children[$1 - 1].nextSibling;\
+                var wrap = function(first, count) {\
+                    var span = document.createElement('span');\
+                    parent.insertBefore(span, first);\
+                    for (var i = 0; i < count; i++) { span.appendChild(span.nextSibling); }\
+                    return span;\
+                };\
+                var claimed = null;\
+                if (kind == 1 && value.length == 0) {\
+                    claimed = document.createElement('span');\
+                    claimed.appendChild(document.createTextNode(''));\
+                    parent.insertBefore(claimed, node);\
+                }\
+                while (node && !claimed) {\
+                    if (kind == 0) {\
+                        if (node.nodeType == 1 && node.localName == value &&\
+                            node.namespaceURI == ns) { claimed = node; break; }\
+                    } else if (kind == 1) {\
+                        if (node.nodeType == 3 && node.data.slice(0, value.length) == value) {\
+                            if (node.data.length > value.length) { node.splitText(value.length); }\
+                            claimed = wrap(node, 1);\
+                            break;\
+                        }\
+                    } else {\
+                        var template = document.createElement('span');\
+                        template.innerHTML = value;\
+                        var count = template.childNodes.length;\
+                        var current = node;\
+                        for (var i = 0; i < count && current; i++) {\
+                            if (!current.isEqualNode(template.childNodes[i])) { break; }\
+                            current = current.nextSibling;\
+                        }\
+                        if (i == count) { claimed = wrap(node, count); break; }\
+                    }\
+                    if (node.nodeType == 8 || (node.nodeType == 3 && !/\\S/.test(node.data))) {\
+                        var next = node.nextSibling;\
+                        parent.removeChild(node);\
+                        node = next;\
+                    } else {\
+                        break;\
+                    }\
+                }\
+                if (!claimed) { return -1; }\
+                var index = __domafic_pool_free.pop();\
+                if (index) { __domafic_pool[index] = claimed; return index; }\
+                return __domafic_pool.push(claimed) - 1;\
+            \0";
+            let text_cstring = CString::new(text).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                index as libc::c_int,
+                kind,
+                text_cstring.as_ptr() as libc::c_int,
+                namespace.as_ref().map_or(0, |ns| ns.as_ptr() as libc::c_int)
+            )
+        };
+        if id < 0 { None } else { Some(WebElement(id)) }
+    }
+
+    /// Returns whether the element's attributes are exactly `attributes`, which don't include
+    /// any `Bool(false)` values.
+    pub(super) fn has_attributes(&self, attributes: &[(&str, &AttributeValue)]) -> bool {
+        attributes.len() == self.attribute_count() && attributes.iter().all(|&(key, value)| {
+            // Boolean attributes are written without a value
+            let value = match *value {
+                AttributeValue::Bool(true) => String::new(),
+                ref value => value.to_string(),
+            };
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = __domafic_pool[$0];\
+                    return elem.getAttribute(UTF8ToString($1)) === UTF8ToString($2) ? 1 : 0;\
+                \0";
+                let key_cstring = CString::new(key).unwrap();
+                let value_cstring = CString::new(value).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.0,
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ptr() as libc::c_int
+                ) != 0
+            }
+        })
+    }
+
+    fn attribute_count(&self) -> usize {
+        unsafe {
+            const JS: &'static [u8] = b"return __domafic_pool[$0].attributes.length;\0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, self.0) as usize
+        }
+    }
+
+    /// Returns the names of the element's attributes.
+    pub(super) fn attribute_names(&self) -> Vec<String> {
+        (0..self.attribute_count()).map(|index| unsafe {
+            const JS: &'static [u8] = b"\
+                var name = __domafic_pool[$0].attributes[$1].name;\
+                return allocate(intArrayFromString(name), 'i8', ALLOC_NORMAL);\
+            \0";
+            let name = emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                index as libc::c_int
+            ) as *mut libc::c_char;
+            let owned = CStr::from_ptr(name).to_string_lossy().into_owned();
+            libc::free(name as *mut libc::c_void);
+            owned
+        }).collect()
+    }
+
+    /// Removes any server-rendered nodes following the first `index` children, warning about
+    /// any that aren't whitespace or comments.
+    pub(super) fn remove_unclaimed_children(&self, index: usize) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var parent = __domafic_pool[$0];\
+                var node = $1 == 0 ? parent.firstChild : parent.children[$1 - 1].nextSibling;\
+                while (node) {\
+                    var next = node.nextSibling;\
+                    if (node.nodeType != 8 && !(node.nodeType == 3 && !/\\S/.test(node.data))) {\
+                        console.warn('domafic: removing unexpected server-rendered node', node);\
+                    }\
+                    parent.removeChild(node);\
+                    node = next;\
+                }\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                index as libc::c_int
+            );
+        }
+    }
+
+    pub(super) fn warn_hydration_mismatch(&self, index: usize, expected: DomValue) {
+        let expected = match expected {
+            DomValue::Element { tag, .. } => format!("<{}> element", tag),
+            DomValue::Text(text) => format!("text {:?}", text),
+            DomValue::RawHtml(markup) => format!("raw HTML {:?}", markup),
+            DomValue::FlushPoint => "flush point".to_string(),
+        };
+        unsafe {
+            const JS: &'static [u8] = b"\
+                console.warn('domafic: hydration mismatch in', __domafic_pool[$0],\
+                    'at child ' + $1 + ': expected ' + UTF8ToString($2));\
+            \0";
+            let expected_cstring = CString::new(expected).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                index as libc::c_int,
+                expected_cstring.as_ptr() as libc::c_int
+            );
+        }
+    }
+
+    // Text nodes are wrapped in a `span` by `create_text_node`
+    pub(super) fn set_text(&self, text: &str) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                __domafic_pool[$0].firstChild.nodeValue = UTF8ToString($1);\
+            \0";
+            let text_cstring = CString::new(text).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                text_cstring.as_ptr() as libc::c_int
+            );
+        }
+    }
+
+    pub(super) fn remove_self(&self) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                if (elem.parentNode) { elem.parentNode.removeChild(elem); }\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+            );
+        }
+    }
+
+    pub(super) fn remove_attribute(&self, key: &str) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                var key = UTF8ToString($1);\
+                var prefix = key.split(':')[0];\
+                if (key.indexOf(':') > 0 && __domafic_attr_ns[prefix]) {\
+                    elem.removeAttributeNS(__domafic_attr_ns[prefix], key.slice(prefix.length + 1));\
+                } else {\
+                    elem.removeAttribute(key);\
+                }\
+            \0";
+            let key_cstring = CString::new(key).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                key_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
+
+    pub(super) fn set_attribute(&self, key: &str, value: &AttributeValue) {
+        // Boolean attributes are enabled by their presence, regardless of their value
+        let value = match *value {
+            AttributeValue::Bool(true) => String::new(),
+            AttributeValue::Bool(false) => return self.remove_attribute(key),
+            ref value => value.to_string(),
+        };
+
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                var key = UTF8ToString($1);\
+                var value = UTF8ToString($2);\
+                var prefix = key.split(':')[0];\
+                if (key.indexOf(':') > 0 && __domafic_attr_ns[prefix]) {\
+                    elem.setAttributeNS(__domafic_attr_ns[prefix], key, value);\
+                } else {\
+                    elem.setAttribute(key, value);\
+                }\
+            \0";
+            let key_cstring = CString::new(key).unwrap();
+            let value_cstring = CString::new(value).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                key_cstring.as_ptr() as libc::c_int,
+                value_cstring.as_ptr() as libc::c_int
+            );
+        }
+    }
+
+    pub(super) fn remove_property(&self, key: &str) {
+        unsafe {
+            // Reset the property to the empty value of its type
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                var key = UTF8ToString($1);\
+                var value = elem[key];\
+                elem[key] = typeof value === 'boolean' ? false :\
+                    (typeof value === 'number' ? 0 : '');\
+            \0";
+            let key_cstring = CString::new(key).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                key_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
+
+    pub(super) fn set_property(&self, key_value: &KeyValue) {
+        unsafe {
+            // Only assign the property if it has changed, so that the element (such as the cursor
+            // of an `input`) isn't disturbed
+            const JS: &'static [u8] = b"\
+                var elem = __domafic_pool[$0];\
+                var key = UTF8ToString($1);\
+                var value = UTF8ToString($2);\
+                if ($3 == 1) { value = value == 'true'; }\
+                else if ($3 == 2) { value = Number(value); }\
+                if (elem[key] !== value) { elem[key] = value; }\
+            \0";
+            let key_cstring = CString::new(key_value.0).unwrap();
+            let value_cstring = CString::new(key_value.1.to_string()).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+                key_cstring.as_ptr() as libc::c_int,
+                value_cstring.as_ptr() as libc::c_int,
+                property_kind(&key_value.1)
+            );
+        }
+    }
+}
+
+impl Drop for WebElement {
+    fn drop(&mut self) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                delete __domafic_pool[$0];\
+                __domafic_pool_free.push($0);\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                self.0,
+            );
+        }
+    }
+}
+
+/// Returns the JSON contents of the element with the id `STATE_SCRIPT_ID`, if there is one
+#[cfg(feature = "use_serde")]
+pub(super) fn state_json() -> Option<String> {
+    use state::STATE_SCRIPT_ID;
+
+    unsafe {
+        const JS: &'static [u8] = b"\
+            var elem = document.getElementById(UTF8ToString($0));\
+            if (!elem) { return 0; }\
+            return allocate(intArrayFromString(elem.textContent), 'i8', ALLOC_NORMAL);\
+        \0";
+        let id_cstring = CString::new(STATE_SCRIPT_ID).unwrap();
+        let json_ptr = emscripten_asm_const_int(
+            &JS[0] as *const _ as *const libc::c_char,
+            id_cstring.as_ptr() as libc::c_int
+        ) as *mut libc::c_char;
+        if json_ptr.is_null() {
+            return None;
+        }
+
+        let json = CStr::from_ptr(json_ptr).to_str().ok().map(|json| json.to_string());
+        libc::free(json_ptr as *mut c_void);
+        json
+    }
+}
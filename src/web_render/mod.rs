@@ -0,0 +1,507 @@
+use DomNode;
+use keys::KeyIter;
+
+/// `Updater`s modify the current application state based on messages.
+pub trait Updater<State, Message>: Sized {
+    /// Modify the application state based on a message.
+    ///
+    /// `KeyIter` may be used to identify which component the message originated from.
+    fn update(&self, &mut State, Message, KeyIter, &JsIo<Message>);
+}
+
+impl<F, S, M> Updater<S, M> for F
+    where F: Fn(&mut S, M, KeyIter, &JsIo<M>) -> ()
+{
+    fn update(
+        &self,
+        state: &mut S,
+        msg: M,
+        keys: KeyIter,
+        js_io: &JsIo<M>
+    ) {
+        (self)(state, msg, keys, js_io)
+    }
+}
+
+/// `Renderer`s convert the current state to the current UI `DomNode`.
+pub trait Renderer<State, Message> {
+
+    // Note: this should really be Rendered<'a>: DomNode + 'a
+    // to allow for references to bits of state, but this is
+    // impossible without ATCs
+    /// Type of the rendered `DomNode`
+    type Rendered: DomNode<Message>;
+
+    /// Renders a `DomNode` given the current application state
+    fn render(&self, &State) -> Self::Rendered;
+}
+
+impl<F, S, R, M> Renderer<S, M> for F where F: Fn(&S) -> R, R: DomNode<M> {
+    type Rendered = R;
+    fn render(&self, state: &S) -> Self::Rendered {
+        (self)(state)
+    }
+}
+
+pub use self::private::{run, hydrate, JsIo, HttpRequest, HttpResponse, HttpResult};
+#[cfg(feature = "use_serde")]
+pub use self::private::serialized_state;
+
+// Calls into the browser, using inline JavaScript when compiled with emscripten
+#[cfg(target_os = "emscripten")]
+mod emscripten;
+#[cfg(target_os = "emscripten")]
+use self::emscripten as js;
+
+// Calls into the browser, using the functions in `js/domafic.js` when compiled for plain `wasm32`
+#[cfg(not(target_os = "emscripten"))]
+mod wasm;
+#[cfg(not(target_os = "emscripten"))]
+use self::wasm as js;
+
+mod private {
+
+    #[cfg(feature = "use_serde")]
+    extern crate serde;
+    #[cfg(feature = "use_serde")]
+    extern crate serde_json;
+
+    use super::{Updater, Renderer};
+    use super::js;
+    use {AttributeValue, DomNode, DomValue, Event, KeyIter, KeyValue, Listener, Namespace};
+    use diff::{Backend, Reconciler};
+    use keys::Keys;
+
+    // This module as a whole is "use_std"-only, so these don't need to be cfg'd
+    use std::marker::PhantomData;
+    use std::os::raw::{c_int, c_void};
+    use std::ptr;
+
+    /// Runs the application (`updater`, `renderer`, `initial_state`) on the webpage under the element
+    /// specified by `element_selector`.
+    pub fn run<D, M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> !
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        start(element_selector, updater, renderer, initial_state, false)
+    }
+
+    /// Runs the application (`updater`, `renderer`, `initial_state`) on top of HTML that was
+    /// already rendered on the server under the element specified by `element_selector`.
+    ///
+    /// Rather than discarding the server-rendered HTML, the existing DOM is reused for the first
+    /// render: matching elements and text are adopted in place and have listeners attached to them.
+    /// `initial_state` should be the same state that was used to render the page on the server.
+    ///
+    /// Whitespace between elements (as written by `HtmlFormat::pretty`) is ignored. Any other
+    /// difference between the server-rendered HTML and the first render is reported as a warning
+    /// in the browser console, after which the affected elements are rendered from scratch.
+    /// Adopted elements whose attributes differ from the first render are also reported, and
+    /// then have their attributes set to the values from the first render, with any others
+    /// removed.
+    pub fn hydrate<D, M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S)
+        -> !
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        start(element_selector, updater, renderer, initial_state, true)
+    }
+
+    /// Reads the application state that was embedded in the page by the server using
+    /// `state::state_script` or `Document::with_state`.
+    ///
+    /// Pass the result to `hydrate` to start from exactly the state that produced the server's
+    /// HTML. Returns `None` if no state was embedded or if it could not be deserialized.
+    ///
+    /// Example:
+    ///
+    /// ```rust,ignore
+    /// hydrate("body", update, render, serialized_state().unwrap_or_else(State::new));
+    /// ```
+    #[cfg(feature = "use_serde")]
+    pub fn serialized_state<S: serde::de::DeserializeOwned>() -> Option<S> {
+        js::state_json().and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    fn start<D, M, U, R, S>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        initial_state: S,
+        hydrating: bool) -> !
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        unsafe {
+            // Get initial DomNode
+            let rendered = renderer.render(&initial_state);
+
+            // Initialize the browser system
+            js::web_init();
+            let document = WebDocument(());
+            let root_node_element =
+                document.element_from_selector(element_selector)
+                    .expect(&format!(
+                        "Target element of `run` was not found: {}", element_selector));
+
+            if !hydrating {
+                root_node_element.remove_all_children();
+            }
+
+            let backend = WebBackend {
+                document: document,
+                root: root_node_element.0,
+                delegated_events: Vec::new(),
+                system_ptr: ptr::null_mut(),
+                marker: PhantomData,
+            };
+
+            // Lives forever, referenced and mutated in callbacks
+            let app_system_mut_ptr: *mut AppSystem<D, M, U, R, S> = Box::into_raw(Box::new((
+                updater,
+                renderer,
+                initial_state,
+                Reconciler::new(backend, root_node_element),
+            )));
+
+            // Draw initial DomNode to browser
+            let reconciler = &mut (*app_system_mut_ptr).3;
+            reconciler.backend_mut().system_ptr = app_system_mut_ptr as *mut c_void;
+            if hydrating {
+                reconciler.hydrate(rendered);
+            } else {
+                reconciler.render(rendered);
+            }
+
+            js::run_main_web_loop()
+        }
+    }
+
+    // Updater, renderer, state, and the reconciler holding the rendered DomNode
+    type AppSystem<D, M, U, R, S> = (U, R, S, Reconciler<M, D, WebBackend<D, M, U, R, S>>);
+
+    struct JsIoImpl<D, M, U, R, S>
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        app_system: *mut AppSystem<D, M, U, R, S>
+    }
+
+    /// A single HTTP request
+    #[derive(Debug, Copy, Clone)]
+    pub struct HttpRequest<'a> {
+        /// HTTP Method ("GET", "POST", etc.)
+        pub method: &'a str,
+        /// A list of HTTP header (key, value) pairs
+        pub headers: &'a [(&'a str, &'a str)],
+        /// Request URL
+        pub url: &'a str,
+        /// Request body
+        pub body: &'a str,
+        /// Optional request timeout in milliseconds
+        pub timeout_millis: Option<u32>,
+    }
+
+    /// HTTP request `Result` indicating a possible network error or timeout
+    pub type HttpResult<'a> = Result<HttpResponse<'a>, HttpError>;
+
+    /// A single HTTP response
+    #[derive(Debug, Copy, Clone)]
+    pub struct HttpResponse<'a> {
+        /// HTTP status
+        pub status_code: u16,
+        /// HTTP status text
+        pub status_text: &'a str,
+        /// A list of HTTP response header (key, value) pairs
+        pub headers: &'a [(&'a str, &'a str)],
+        /// The body of the HTTP response
+        pub body: &'a str,
+    }
+
+    /// HTTP request error indicating either a network connection error or a timeout
+    #[derive(Debug, Copy, Clone)]
+    pub enum HttpError {
+        NetworkError,
+        Timeout,
+    }
+
+    /// Handler for an HTTP response
+    pub trait HttpResponseHandler: 'static {
+        type Message;
+        fn handle<'a>(&self, HttpResult<'a>) -> Self::Message;
+    }
+    impl<F, Message> HttpResponseHandler for F
+        where F: for<'a> Fn(HttpResult<'a>) -> Message + 'static
+    {
+        type Message = Message;
+        fn handle<'a>(&self, response: HttpResult<'a>) -> Message {
+            (self)(response)
+        }
+    }
+
+    /// JavaScript IO interface
+    pub trait JsIo<Message> {
+        /// Issue an asynchronous HTTP request
+        fn http<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            handler: Box<HttpResponseHandler<Message=Message>>,
+        );
+    }
+
+    impl<D, M, U, R, S> JsIo<M> for JsIoImpl<D, M, U, R, S>
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        fn http<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            handler: Box<HttpResponseHandler<Message=M>>,
+        ) {
+            js::http::<D, M, U, R, S>(self.app_system as *mut c_void, http_request, handler)
+        }
+    }
+
+    /// Parses an HTTP response, passes it to `handler` and updates the application with the result.
+    ///
+    /// `error_sig` is 0 for a successful request, 1 for a timeout and 2 for a network error.
+    pub(super) unsafe fn deliver_http_result<D, M, U, R, S>(
+        system_c_ptr: *mut c_void,
+        handler: Box<HttpResponseHandler<Message=M>>,
+        error_sig: c_int,
+        status_code: u16,
+        status_text: &str,
+        body: &str,
+        headers: &str,
+    )
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>,
+    {
+        let headers = headers.split("\r\n").flat_map(|header| {
+            header.find(':').map(|split_index| {
+                let (key, value) = header.split_at(split_index);
+                (key.trim(), value[1..].trim())
+            })
+        }).collect::<Vec<_>>();
+
+        let response_result = match error_sig {
+            0 => Ok(HttpResponse {
+                status_code: status_code,
+                status_text: status_text,
+                headers: &headers,
+                body: body,
+            }),
+
+            1 => Err(HttpError::Timeout),
+
+            2 => Err(HttpError::NetworkError),
+
+            _ => unreachable!(),
+        };
+
+        let message = handler.handle(response_result);
+
+        update_system::<D, M, U, R, S>(system_c_ptr, message, Keys::new());
+    }
+
+    /// Sends an event received by the root to the listeners of the node at `path`, and updates
+    /// the application with the resulting messages.
+    pub(super) unsafe fn deliver_event<D, M, U, R, S>(
+        system_c_ptr: *mut c_void,
+        event: Event,
+        path: &[usize],
+        bubbles: bool,
+    )
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>,
+    {
+        let system_ptr = system_c_ptr as *mut AppSystem<D, M, U, R, S>;
+
+        // All of the messages are produced before the first update, which re-renders the
+        // application and replaces the listeners
+        let messages = (*system_ptr).3.dispatch(path, event, bubbles);
+        for (message, keys) in messages {
+            update_system::<D, M, U, R, S>(system_c_ptr, message, keys.into_keys());
+        }
+    }
+
+    pub(super) unsafe fn update_system<D, M, U, R, S>
+    (
+        system_c_ptr: *mut c_void,
+        message: M,
+        keys: Keys
+    )
+        where
+        (D, U, R, S): Sized,
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>,
+    {
+
+        let system_ptr = system_c_ptr as *mut AppSystem<D, M, U, R, S>;
+        let system_ref: &mut AppSystem<D, M, U, R, S> = system_ptr.as_mut().unwrap();
+
+        let (
+            ref mut updater,
+            ref mut renderer,
+            ref mut state,
+            ref mut reconciler,
+        ) = *system_ref;
+
+        // Update state
+        updater.update(state, message, keys.into_iter(), &JsIoImpl { app_system: system_ptr });
+
+        // Render new DomNode and write it to the root element
+        reconciler.render(renderer.render(state));
+    }
+
+    struct WebBackend<D, M, U, R, S> {
+        document: WebDocument,
+        // The root element, which listens for events on behalf of the nodes under it
+        root: JsElementId,
+        // Types of event that the root listens for
+        delegated_events: Vec<&'static str>,
+        // Pointer to the `AppSystem` that owns this backend, passed to listener callbacks
+        system_ptr: *mut c_void,
+        marker: PhantomData<(D, M, U, R, S)>,
+    }
+
+    impl<D, M, U, R, S> Backend<M> for WebBackend<D, M, U, R, S>
+        where
+        D: DomNode<M>,
+        M: 'static,
+        U: Updater<S, M>,
+        R: Renderer<S, M, Rendered=D>
+    {
+        type Node = WebElement;
+        type ListenerHandle = ();
+
+        fn create_element(&mut self, tag: &str, namespace: Namespace) -> WebElement {
+            self.document.create_element(tag, namespace).unwrap()
+        }
+
+        fn create_text(&mut self, text: &str) -> WebElement {
+            self.document.create_text_node(text).unwrap()
+        }
+
+        fn create_raw_html(&mut self, markup: &str) -> WebElement {
+            self.document.create_raw_html(markup).unwrap()
+        }
+
+        fn set_text(&mut self, node: &WebElement, text: &str) {
+            node.set_text(text)
+        }
+
+        fn insert(&mut self, parent: &WebElement, index: usize, child: &WebElement) {
+            parent.insert(index, child)
+        }
+
+        fn move_child(&mut self, parent: &WebElement, old_index: usize, new_index: usize) {
+            parent.move_child(old_index, new_index)
+        }
+
+        fn remove(&mut self, _parent: &WebElement, child: &WebElement) {
+            child.remove_self()
+        }
+
+        fn set_attribute(&mut self, node: &WebElement, name: &str, value: &AttributeValue) {
+            node.set_attribute(name, value)
+        }
+
+        fn remove_attribute(&mut self, node: &WebElement, name: &str) {
+            node.remove_attribute(name)
+        }
+
+        fn set_property(&mut self, node: &WebElement, property: &KeyValue) {
+            node.set_property(property)
+        }
+
+        fn remove_property(&mut self, node: &WebElement, name: &str) {
+            node.remove_property(name)
+        }
+
+        // Events are delegated to the root, which routes them to the current render's listeners
+        // with `Reconciler::dispatch`, so each type of event only needs to be listened for once
+        unsafe fn add_listener(
+            &mut self,
+            _node: &WebElement,
+            event_type: &'static str,
+            _listener: *const Listener<M>,
+            _keys: KeyIter,
+        ) {
+            if !self.delegated_events.contains(&event_type) {
+                js::delegate::<D, M, U, R, S>(self.root, event_type, self.system_ptr);
+                self.delegated_events.push(event_type);
+            }
+        }
+
+        fn remove_listener(&mut self, _node: &WebElement, _event_type: &'static str, _listener: ()) {}
+
+        fn claim_child(&mut self, parent: &WebElement, index: usize, value: DomValue)
+            -> Option<WebElement>
+        {
+            parent.claim_child(index, value)
+        }
+
+        fn claimed_attributes_match(
+            &self,
+            node: &WebElement,
+            attributes: &[(&str, &AttributeValue)],
+        ) -> bool {
+            node.has_attributes(attributes)
+        }
+
+        fn claimed_attribute_names(&self, node: &WebElement) -> Vec<String> {
+            node.attribute_names()
+        }
+
+        fn remove_unclaimed_children(&mut self, parent: &WebElement, index: usize) {
+            parent.remove_unclaimed_children(index)
+        }
+
+        fn hydration_mismatch(&mut self, parent: &WebElement, index: usize, value: DomValue) {
+            parent.warn_hydration_mismatch(index, value)
+        }
+    }
+
+    pub(super) type JsElementId = c_int;
+
+    /// Property values are passed to JS as strings, along with the type to convert them back to:
+    /// 0 for strings, 1 for booleans and 2 for numbers
+    pub(super) fn property_kind(value: &AttributeValue) -> c_int {
+        match *value {
+            AttributeValue::Bool(_) => 1,
+            AttributeValue::Int(_) | AttributeValue::Float(_) => 2,
+            _ => 0,
+        }
+    }
+
+    /// A handle to a DOM node, which is released when dropped
+    #[derive(Debug)]
+    pub(super) struct WebElement(pub(super) JsElementId);
+
+    #[derive(Debug, Copy, Clone)]
+    pub(super) struct WebDocument(()); // Contains private () so that it can't be created externally
+}
@@ -0,0 +1,428 @@
+use super::{Updater, Renderer};
+use super::private::{HttpRequest, HttpResponseHandler, JsElementId, WebDocument, WebElement};
+use super::private::{deliver_event, deliver_http_result, property_kind};
+use {AttributeValue, DomNode, DomValue, Event, KeyValue, Namespace};
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::mem;
+use std::os::raw::c_void;
+
+/// Functions provided by `js/domafic.js`.
+///
+/// Strings are passed as a pointer and a length in bytes. Strings returned to Rust are written
+/// into a buffer allocated by the caller: `arg_str` and `state_json` return the length of the
+/// pending string (or -1 if there isn't one), which is then copied out with `take_str`.
+mod ffi {
+    use super::JsElementId;
+    use std::os::raw::c_int;
+
+    #[link(wasm_import_module = "domafic")]
+    extern "C" {
+        pub fn unwind() -> !;
+
+        pub fn element_from_selector(selector: *const u8, selector_len: usize) -> JsElementId;
+        pub fn create_element(
+            tag: *const u8, tag_len: usize, namespace: *const u8, namespace_len: usize
+        ) -> JsElementId;
+        pub fn create_text_node(text: *const u8, text_len: usize) -> JsElementId;
+        pub fn create_raw_html(markup: *const u8, markup_len: usize) -> JsElementId;
+        pub fn release(element: JsElementId);
+
+        pub fn insert(parent: JsElementId, index: usize, child: JsElementId) -> i32;
+        pub fn move_child(parent: JsElementId, old_index: usize, new_index: usize) -> i32;
+        pub fn remove_self(element: JsElementId);
+        pub fn remove_all_children(element: JsElementId);
+
+        pub fn delegate(
+            root: JsElementId,
+            event_name: *const u8,
+            event_name_len: usize,
+            callback: usize,
+            context: usize,
+        );
+
+        pub fn claim_child(
+            parent: JsElementId,
+            index: usize,
+            kind: i32,
+            value: *const u8,
+            value_len: usize,
+            namespace: *const u8,
+            namespace_len: usize,
+        ) -> JsElementId;
+        pub fn attribute_count(element: JsElementId) -> usize;
+        pub fn attribute_name(element: JsElementId, index: usize) -> i32;
+        pub fn has_attribute(
+            element: JsElementId,
+            key: *const u8,
+            key_len: usize,
+            value: *const u8,
+            value_len: usize,
+        ) -> i32;
+        pub fn remove_unclaimed_children(parent: JsElementId, index: usize);
+        pub fn warn_hydration_mismatch(
+            parent: JsElementId, index: usize, expected: *const u8, expected_len: usize
+        );
+
+        pub fn set_text(element: JsElementId, text: *const u8, text_len: usize);
+        pub fn set_attribute(
+            element: JsElementId,
+            key: *const u8,
+            key_len: usize,
+            value: *const u8,
+            value_len: usize,
+        );
+        pub fn remove_attribute(element: JsElementId, key: *const u8, key_len: usize);
+        pub fn set_property(
+            element: JsElementId,
+            key: *const u8,
+            key_len: usize,
+            value: *const u8,
+            value_len: usize,
+            kind: c_int,
+        );
+        pub fn remove_property(element: JsElementId, key: *const u8, key_len: usize);
+
+        pub fn http(
+            method: *const u8,
+            method_len: usize,
+            url: *const u8,
+            url_len: usize,
+            body: *const u8,
+            body_len: usize,
+            headers: *const u8,
+            headers_len: usize,
+            timeout_millis: u32,
+            callback: usize,
+            context: usize,
+        );
+
+        pub fn arg_int(index: u32) -> i32;
+        pub fn arg_str(index: u32) -> i32;
+        #[cfg(feature = "use_serde")]
+        pub fn state_json(id: *const u8, id_len: usize) -> i32;
+        pub fn take_str(buffer: *mut u8);
+    }
+}
+
+/// Entry point for all callbacks from JavaScript (listeners and HTTP responses).
+///
+/// `callback` is an `unsafe fn(usize, i32)` which is called with `context` and `arg`.
+#[doc(hidden)]
+#[no_mangle]
+pub unsafe extern "C" fn __domafic_callback(callback: usize, context: usize, arg: i32) {
+    let callback: unsafe fn(usize, i32) = mem::transmute(callback);
+    callback(context, arg)
+}
+
+/// Copies out the string left pending by the JavaScript side, given its length
+unsafe fn take_str(len: i32) -> Option<String> {
+    if len < 0 {
+        return None;
+    }
+    let mut buffer = vec![0; len as usize];
+    ffi::take_str(buffer.as_mut_ptr());
+    String::from_utf8(buffer).ok()
+}
+
+struct HttpContext<M> {
+    handler: Box<dyn HttpResponseHandler<Message=M>>,
+    system_ptr: *mut c_void,
+}
+
+/// Issues an HTTP request, passing the response to `handler` and then updating the application
+pub(super) fn http<D, M, U, R, S>(
+    system_c_ptr: *mut c_void,
+    http_request: HttpRequest,
+    handler: Box<dyn HttpResponseHandler<Message=M>>,
+)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+{
+    let HttpRequest { method, headers, url, body, timeout_millis } = http_request;
+    let headers = headers.iter()
+        .map(|&(key, value)| format!("{}: {}\r\n", key, value))
+        .collect::<String>();
+
+    // Reclaimed by `handle_http_result`
+    let context = Box::into_raw(Box::new(HttpContext {
+        handler,
+        system_ptr: system_c_ptr,
+    }));
+
+    unsafe {
+        ffi::http(
+            method.as_ptr(), method.len(),
+            url.as_ptr(), url.len(),
+            body.as_ptr(), body.len(),
+            headers.as_ptr(), headers.len(),
+            timeout_millis.unwrap_or(0),
+            handle_http_result::<D, M, U, R, S> as *const () as usize,
+            context as usize,
+        );
+    }
+}
+
+unsafe fn handle_http_result<D, M, U, R, S>(context: usize, error_sig: i32)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>,
+{
+    let context = Box::from_raw(context as *mut HttpContext<M>);
+    let HttpContext { handler, system_ptr } = *context;
+
+    let (status_code, status_text, body, headers) = if error_sig == 0 {
+        (
+            ffi::arg_int(0) as u16,
+            take_str(ffi::arg_str(1)).unwrap_or_default(),
+            take_str(ffi::arg_str(2)).unwrap_or_default(),
+            take_str(ffi::arg_str(3)).unwrap_or_default(),
+        )
+    } else {
+        (0, String::new(), String::new(), String::new())
+    };
+
+    deliver_http_result::<D, M, U, R, S>(
+        system_ptr, handler, error_sig, status_code, &status_text, &body, &headers);
+}
+
+pub(super) fn web_init() {}
+
+pub(super) fn run_main_web_loop() -> ! {
+    // Throws back to `domafic.load`, leaving the application running on the JavaScript event loop
+    unsafe { ffi::unwind() }
+}
+
+fn element_from_id(id: JsElementId) -> Option<WebElement> {
+    if id < 0 { None } else { Some(WebElement(id)) }
+}
+
+impl WebDocument {
+    pub(super) fn element_from_selector(&self, selector: &str) -> Option<WebElement> {
+        element_from_id(unsafe { ffi::element_from_selector(selector.as_ptr(), selector.len()) })
+    }
+
+    pub(super) fn create_element(&self, tagname: &str, namespace: Namespace) -> Option<WebElement> {
+        let namespace = match namespace {
+            Namespace::Html => "",
+            _ => namespace.uri(),
+        };
+        element_from_id(unsafe {
+            ffi::create_element(tagname.as_ptr(), tagname.len(), namespace.as_ptr(), namespace.len())
+        })
+    }
+
+    pub(super) fn create_text_node(&self, text: &str) -> Option<WebElement> {
+        element_from_id(unsafe { ffi::create_text_node(text.as_ptr(), text.len()) })
+    }
+
+    pub(super) fn create_raw_html(&self, markup: &str) -> Option<WebElement> {
+        element_from_id(unsafe { ffi::create_raw_html(markup.as_ptr(), markup.len()) })
+    }
+}
+
+/// Listens for events of type `event_name` on the element `root`, passing each one to
+/// `deliver_event` along with the path from `root` to the event's target.
+///
+/// Requires that `system_ptr` is valid and that `root` is a valid `WebElement` id throughout the
+/// duration of time that it is possible for this callback to be triggered.
+pub(super) unsafe fn delegate<D, M, U, R, S>(
+    root: JsElementId,
+    event_name: &str,
+    system_ptr: *mut c_void,
+)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+{
+    ffi::delegate(
+        root,
+        event_name.as_ptr(),
+        event_name.len(),
+        handle_event::<D, M, U, R, S> as *const () as usize,
+        system_ptr as usize,
+    )
+}
+
+unsafe fn handle_event<D, M, U, R, S>(system_ptr: usize, _arg: i32)
+    where
+    D: DomNode<M>,
+    M: 'static,
+    U: Updater<S, M>,
+    R: Renderer<S, M, Rendered=D>
+{
+    let type_str = take_str(ffi::arg_str(0));
+    let target_value = take_str(ffi::arg_str(1));
+    let event = Event {
+        type_str: type_str.as_ref().map(|type_str| &type_str[..]),
+        target_value: target_value.as_ref().map(|target_value| &target_value[..]),
+        client_x: ffi::arg_int(2),
+        client_y: ffi::arg_int(3),
+        offset_x: ffi::arg_int(4),
+        offset_y: ffi::arg_int(5),
+        which_keycode: ffi::arg_int(6),
+        shift_key: ffi::arg_int(7) == 1,
+        alt_key: ffi::arg_int(8) == 1,
+        ctrl_key: ffi::arg_int(9) == 1,
+        meta_key: ffi::arg_int(10) == 1,
+    };
+
+    // The path from the root to the target follows its length
+    let bubbles = ffi::arg_int(11) == 1;
+    let path = (0..ffi::arg_int(12) as u32)
+        .map(|index| ffi::arg_int(13 + index) as usize)
+        .collect::<Vec<_>>();
+
+    deliver_event::<D, M, U, R, S>(system_ptr as *mut c_void, event, &path, bubbles);
+}
+
+impl WebElement {
+    pub(super) fn insert(&self, index: usize, child: &WebElement) {
+        let err = unsafe { ffi::insert(self.0, index, child.0) };
+
+        // Must panic on error because failure to properly add/remove nodes
+        // containing listeners can cause memory unsafety
+        if err < 0 { panic!("Attempted to insert child DOM element out of bounds") }
+    }
+
+    pub(super) fn move_child(&self, old_index: usize, new_index: usize) {
+        let err = unsafe { ffi::move_child(self.0, old_index, new_index) };
+
+        // Must panic on error because failure to properly add/remove nodes
+        // containing listeners can cause memory unsafety
+        if err < 0 { panic!("Attempted to move child DOM element out of bounds") }
+    }
+
+    pub(super) fn remove_all_children(&self) {
+        unsafe { ffi::remove_all_children(self.0) }
+    }
+
+    /// Finds the server-rendered node matching `value` following the first `index` children,
+    /// which have already been claimed, and claims it.
+    pub(super) fn claim_child(&self, index: usize, value: DomValue) -> Option<WebElement> {
+        let (kind, text, namespace) = match value {
+            DomValue::Element { tag, namespace } => (0, tag, match namespace {
+                Namespace::Html => "",
+                _ => namespace.uri(),
+            }),
+            DomValue::Text(text) => (1, text, ""),
+            DomValue::RawHtml(markup) => (2, markup, ""),
+            DomValue::FlushPoint => return None,
+        };
+        element_from_id(unsafe {
+            ffi::claim_child(
+                self.0, index, kind,
+                text.as_ptr(), text.len(),
+                namespace.as_ptr(), namespace.len()
+            )
+        })
+    }
+
+    /// Returns whether the element's attributes are exactly `attributes`, which don't include
+    /// any `Bool(false)` values.
+    pub(super) fn has_attributes(&self, attributes: &[(&str, &AttributeValue)]) -> bool {
+        attributes.len() == unsafe { ffi::attribute_count(self.0) } &&
+            attributes.iter().all(|&(key, value)| {
+                // Boolean attributes are written without a value
+                let value = match *value {
+                    AttributeValue::Bool(true) => String::new(),
+                    ref value => value.to_string(),
+                };
+                unsafe {
+                    ffi::has_attribute(
+                        self.0,
+                        key.as_ptr(), key.len(),
+                        value.as_ptr(), value.len()
+                    ) != 0
+                }
+            })
+    }
+
+    /// Returns the names of the element's attributes.
+    pub(super) fn attribute_names(&self) -> Vec<String> {
+        (0..unsafe { ffi::attribute_count(self.0) })
+            .filter_map(|index| unsafe { take_str(ffi::attribute_name(self.0, index)) })
+            .collect()
+    }
+
+    /// Removes any server-rendered nodes following the first `index` children, warning about
+    /// any that aren't whitespace or comments.
+    pub(super) fn remove_unclaimed_children(&self, index: usize) {
+        unsafe { ffi::remove_unclaimed_children(self.0, index) }
+    }
+
+    pub(super) fn warn_hydration_mismatch(&self, index: usize, expected: DomValue) {
+        let expected = match expected {
+            DomValue::Element { tag, .. } => format!("<{}> element", tag),
+            DomValue::Text(text) => format!("text {:?}", text),
+            DomValue::RawHtml(markup) => format!("raw HTML {:?}", markup),
+            DomValue::FlushPoint => "flush point".to_string(),
+        };
+        unsafe { ffi::warn_hydration_mismatch(self.0, index, expected.as_ptr(), expected.len()) }
+    }
+
+    pub(super) fn set_text(&self, text: &str) {
+        unsafe { ffi::set_text(self.0, text.as_ptr(), text.len()) }
+    }
+
+    pub(super) fn remove_self(&self) {
+        unsafe { ffi::remove_self(self.0) }
+    }
+
+    pub(super) fn remove_attribute(&self, key: &str) {
+        unsafe { ffi::remove_attribute(self.0, key.as_ptr(), key.len()) }
+    }
+
+    pub(super) fn set_attribute(&self, key: &str, value: &AttributeValue) {
+        // Boolean attributes are enabled by their presence, regardless of their value
+        let value = match *value {
+            AttributeValue::Bool(true) => String::new(),
+            AttributeValue::Bool(false) => return self.remove_attribute(key),
+            ref value => value.to_string(),
+        };
+        unsafe {
+            ffi::set_attribute(
+                self.0,
+                key.as_ptr(), key.len(),
+                value.as_ptr(), value.len()
+            )
+        }
+    }
+
+    pub(super) fn remove_property(&self, key: &str) {
+        unsafe { ffi::remove_property(self.0, key.as_ptr(), key.len()) }
+    }
+
+    pub(super) fn set_property(&self, key_value: &KeyValue) {
+        let value = key_value.1.to_string();
+        unsafe {
+            ffi::set_property(
+                self.0,
+                key_value.0.as_ptr(), key_value.0.len(),
+                value.as_ptr(), value.len(),
+                property_kind(&key_value.1)
+            )
+        }
+    }
+}
+
+impl Drop for WebElement {
+    fn drop(&mut self) {
+        unsafe { ffi::release(self.0) }
+    }
+}
+
+/// Returns the JSON contents of the element with the id `STATE_SCRIPT_ID`, if there is one
+#[cfg(feature = "use_serde")]
+pub(super) fn state_json() -> Option<String> {
+    use state::STATE_SCRIPT_ID;
+
+    unsafe { take_str(ffi::state_json(STATE_SCRIPT_ID.as_ptr(), STATE_SCRIPT_ID.len())) }
+}